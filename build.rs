@@ -16,6 +16,8 @@ const HTTP_RUST_TEST: &str = "tests/modules/http-rust-hello";
 const NN_TEST: &str = "tests/modules/nn-demo";
 const CACHE_RUST_TEST: &str = "tests/modules/cache-rust";
 const CACHE_CPP_TEST: &str = "tests/modules/cache-cpp";
+const CACHE_CONFORMANCE_TEST: &str = "tests/modules/cache-conformance";
+const CACHE_EVICTION_TEST: &str = "tests/modules/cache-eviction";
 const LOG_RUST_TEST: &str = "tests/modules/rust-log";
 const CLOUDEVENT_TEST: &str = "tests/modules/cloudevent-demo";
 
@@ -27,6 +29,11 @@ fn main() {
     println!("cargo:rerun-if-changed={}/src/lib.rs", CACHE_FS);
     println!("cargo:rerun-if-changed={}/src/lib.rs", CE);
     println!("cargo:rerun-if-changed={}/src/lib.rs", CLOUDEVENT_TEST);
+    println!(
+        "cargo:rerun-if-changed={}/src/lib.rs",
+        CACHE_CONFORMANCE_TEST
+    );
+    println!("cargo:rerun-if-changed={}/src/lib.rs", CACHE_EVICTION_TEST);
 
     check_tools();
 
@@ -36,6 +43,8 @@ fn main() {
 
     cargo_wasi_build(HTTP_RUST_TEST);
     cargo_wasi_build(CACHE_RUST_TEST);
+    cargo_wasi_build(CACHE_CONFORMANCE_TEST);
+    cargo_wasi_build(CACHE_EVICTION_TEST);
     cargo_wasi_build(LOG_RUST_TEST);
     cargo_wasi_build(NN_TEST);
     cargo_wasi_build(CLOUDEVENT_TEST);
@@ -44,6 +53,8 @@ fn main() {
 
     make_link(CACHE_RUST_TEST);
     make_link(CACHE_CPP_TEST);
+    make_link(CACHE_CONFORMANCE_TEST);
+    make_link(CACHE_EVICTION_TEST);
     make_link(CLOUDEVENT_TEST);
 }
 
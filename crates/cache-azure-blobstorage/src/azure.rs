@@ -1,102 +1,372 @@
 //! Implements the low level access to Azure storage.
 
-use azure_core::HttpClient;
+use azure_core::{prelude::Metadata, HttpClient, HttpError};
 use azure_storage::{blob::prelude::*, core::prelude::*};
 use bytes::Bytes;
-use std::{error::Error, result, sync::Arc};
+use std::{
+    collections::HashMap,
+    env,
+    error::Error,
+    num::NonZeroU32,
+    result,
+    sync::Arc,
+    time::{SystemTime, UNIX_EPOCH},
+};
+use url::Url;
 
 /// The storage account name;
 pub const STORAGE_ACCOUNT: &str = "STORAGE_ACCOUNT";
 /// The main storage account access key.
 pub const STORAGE_MASTER_KEY: &str = "STORAGE_MASTER_KEY";
+/// A shared access signature token for the storage account.
+pub const STORAGE_SAS_TOKEN: &str = "STORAGE_SAS_TOKEN";
+/// A connection string for the storage account.
+pub const STORAGE_CONNECTION_STRING: &str = "STORAGE_CONNECTION_STRING";
+/// The blob endpoint of a local storage emulator.
+pub const STORAGE_ENDPOINT: &str = "STORAGE_ENDPOINT";
 /// The container name;
 pub const CONTAINER: &str = "CONTAINER";
 
 /// Type alias for the result used throughout this crate.
 pub type Result<T> = result::Result<T, Box<dyn Error + Send + Sync>>;
 
-/// Write a byte array into a blob, given the storage account, access, and container name.
+/// The name of the blob metadata storing the expiration time, as seconds since the Unix epoch.
+pub const EXPIRES_METADATA: &str = "expires";
+
+/// The size of the blocks of a chunked upload. Larger payloads are uploaded as
+/// a list of blocks of this size, instead of in a single request.
+pub const BLOCK_SIZE: usize = 4 * 1024 * 1024;
+
+/// Options for writing a blob.
+#[derive(Clone, Debug, Default)]
+pub struct WriteOptions {
+    /// The content type of the blob. If not set, it is sniffed from the payload.
+    /// The cache interface has no content type, so the cache never sets it.
+    pub content_type: Option<String>,
+    /// The expiration time of the blob, as seconds since the Unix epoch, if any.
+    pub expires: Option<u64>,
+}
+
+/// Write a byte array into a blob of the container, uploading it in blocks
+/// if it is larger than the block size. Return the number of bytes written.
 pub async fn write_blob(
     blob: String,
     bytes: Vec<u8>,
-    config: &Config,
-    http_client: Arc<Box<dyn HttpClient>>,
-) -> Result<u8> {
-    let blob_client = StorageAccountClient::new_access_key(
-        http_client.clone(),
-        &config.storage_account,
-        &config.storage_account_key,
-    )
-    .as_storage_client()
-    .as_container_client(&config.container)
-    .as_blob_client(blob);
-
-    let len = bytes.len() as u8;
-    println!("Writing {} bytes.", len);
+    options: &WriteOptions,
+    container: &Arc<ContainerClient>,
+) -> Result<usize> {
+    let blob_client = container.as_blob_client(blob);
+
+    let len = bytes.len();
+    let content_type = match &options.content_type {
+        Some(t) => t.as_str(),
+        None => sniff_content_type(&bytes),
+    };
+    let mut metadata = Metadata::new();
+    if let Some(expires) = options.expires {
+        metadata.insert(EXPIRES_METADATA, expires.to_string());
+    }
+
+    if len <= BLOCK_SIZE {
+        blob_client
+            .put_block_blob(bytes)
+            .content_type(content_type)
+            .metadata(&metadata)
+            .execute()
+            .await?;
+        return Ok(len);
+    }
+
+    // Block identifiers must all have the same length within a blob. They are unique
+    // to each upload, so that concurrent uploads of the same blob, which stage their
+    // blocks side by side, only ever commit their own blocks.
+    let upload_id = upload_id()?;
+    let bytes = Bytes::from(bytes);
+    let mut block_list = BlockList::default();
+    for (i, offset) in (0..len).step_by(BLOCK_SIZE).enumerate() {
+        let block_id = BlockId::new(format!("{}-{:08}", upload_id, i));
+        let end = (offset + BLOCK_SIZE).min(len);
+        blob_client
+            .put_block(block_id.clone(), bytes.slice(offset..end))
+            .execute()
+            .await?;
+        block_list
+            .blocks
+            .push(BlobBlockType::new_uncommitted(block_id));
+    }
 
     blob_client
-        .put_block_blob(bytes)
-        .content_type("text/plain")
+        .put_block_list(&block_list)
+        .content_type(content_type)
+        .metadata(&metadata)
         .execute()
         .await?;
-
     Ok(len)
 }
 
-/// Read a byte array from a blob, given the the storage account, access, container, and blob name.
+/// A blob read from the container.
+pub struct StoredBlob {
+    /// The contents of the blob.
+    pub data: Bytes,
+    /// The expiration time of the blob, as seconds since the Unix epoch, if any.
+    pub expires: Option<u64>,
+}
+
+/// Read a blob of the container, with its expiration time.
+/// Return `None` if the blob does not exist, or if it has expired. Expired blobs
+/// are not deleted, since a concurrent write may have replaced the blob since it was read.
 pub async fn read_blob(
     blob: String,
-    config: &Config,
-    http_client: Arc<Box<dyn HttpClient>>,
-) -> Result<Bytes> {
-    let blob_client = StorageAccountClient::new_access_key(
-        http_client.clone(),
-        &config.storage_account,
-        &config.storage_account_key,
-    )
-    .as_storage_client()
-    .as_container_client(&config.container)
-    .as_blob_client(blob);
-
-    Ok(Bytes::from(
-        blob_client.get().execute().await?.data.to_vec(),
-    ))
-}
-
-/// Remove the blob given the storage account, access, container, and blob name.
-pub async fn delete_blob(
-    blob: String,
-    config: &Config,
-    http_client: Arc<Box<dyn HttpClient>>,
-) -> Result<()> {
-    let blob_client = StorageAccountClient::new_access_key(
-        http_client.clone(),
-        &config.storage_account,
-        &config.storage_account_key,
-    )
-    .as_storage_client()
-    .as_container_client(&config.container)
-    .as_blob_client(blob);
+    container: &Arc<ContainerClient>,
+) -> Result<Option<StoredBlob>> {
+    let blob_client = container.as_blob_client(blob);
+
+    let res: Result<_> = blob_client.get().execute().await.map_err(Box::from);
+    let res = match res {
+        Ok(res) => res,
+        Err(e) if is_not_found(e.as_ref()) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    let expires = expiration(res.blob.metadata.as_ref());
+    if is_expired(expires, now()?) {
+        return Ok(None);
+    }
+    Ok(Some(StoredBlob {
+        data: Bytes::from(res.data.to_vec()),
+        expires,
+    }))
+}
+
+/// Remove a blob of the container. Removing a blob that does not exist succeeds.
+pub async fn delete_blob(blob: String, container: &Arc<ContainerClient>) -> Result<()> {
+    let blob_client = container.as_blob_client(blob);
 
-    blob_client.delete().execute().await?;
-    Ok(())
+    let res: Result<_> = blob_client.delete().execute().await.map_err(Box::from);
+    match res {
+        Err(e) if !is_not_found(e.as_ref()) => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// List the names of the blobs of the container starting with the given prefix.
+/// Return the marker of the next page, if there are more blobs.
+pub async fn list_blobs(
+    prefix: String,
+    marker: Option<String>,
+    limit: u32,
+    container: &Arc<ContainerClient>,
+) -> Result<(Vec<String>, Option<String>)> {
+    let max_results = NonZeroU32::new(limit.max(1)).ok_or("invalid page size")?;
+    let mut request = container
+        .list_blobs()
+        .prefix(prefix)
+        .include_metadata(true)
+        .max_results(MaxResults::new(max_results));
+    if let Some(marker) = marker {
+        request = request.next_marker(marker);
+    }
+
+    let res = request.execute().await?;
+    let now = now()?;
+    let names = res
+        .blobs
+        .blobs
+        .into_iter()
+        .filter(|b| !is_expired(expiration(b.metadata.as_ref()), now))
+        .map(|b| b.name)
+        .collect();
+    Ok((names, res.next_marker.map(|m| m.as_str().to_string())))
+}
+
+/// Return the expiration time stored in the metadata of a blob, if it is valid.
+fn expiration(metadata: Option<&HashMap<String, String>>) -> Option<u64> {
+    metadata
+        .and_then(|m| m.get(EXPIRES_METADATA))
+        .and_then(|v| v.parse::<u64>().ok())
+}
+
+/// Check whether a blob with the given expiration time has expired at the given
+/// time, as seconds since the Unix epoch. Blobs without an expiration time never expire.
+fn is_expired(expires: Option<u64>, now: u64) -> bool {
+    expires.is_some_and(|expires| expires <= now)
+}
+
+/// Return a random identifier for an upload, as 32 hexadecimal digits.
+fn upload_id() -> Result<String> {
+    let mut id = [0; 16];
+    getrandom::getrandom(&mut id)?;
+    Ok(id.iter().map(|b| format!("{:02x}", b)).collect())
+}
+
+/// Return the current time as seconds since the Unix epoch.
+pub fn now() -> Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
+
+/// Return the content type of a payload, recognized from its leading bytes.
+/// Payloads that are not recognized are text if they are valid UTF-8, and binary otherwise.
+pub fn sniff_content_type(bytes: &[u8]) -> &'static str {
+    const SIGNATURES: &[(&[u8], &str)] = &[
+        (b"\x89PNG\r\n\x1a\n", "image/png"),
+        (b"\xff\xd8\xff", "image/jpeg"),
+        (b"GIF87a", "image/gif"),
+        (b"GIF89a", "image/gif"),
+        (b"%PDF-", "application/pdf"),
+        (b"\x1f\x8b", "application/gzip"),
+        (b"PK\x03\x04", "application/zip"),
+        (b"\0asm", "application/wasm"),
+    ];
+    if let Some((_, content_type)) = SIGNATURES.iter().find(|(s, _)| bytes.starts_with(s)) {
+        return content_type;
+    }
+
+    match std::str::from_utf8(bytes) {
+        Ok(s) if matches!(s.trim_start().chars().next(), Some('{') | Some('[')) => {
+            "application/json"
+        }
+        Ok(_) => "text/plain; charset=utf-8",
+        Err(_) => "application/octet-stream",
+    }
+}
+
+/// Return the HTTP status code of a failed request, if the error contains one.
+pub fn status_code(err: &(dyn Error + Send + Sync + 'static)) -> Option<u16> {
+    match err.downcast_ref::<HttpError>() {
+        Some(HttpError::UnexpectedStatusCode { received, .. }) => Some(received.as_u16()),
+        _ => None,
+    }
+}
+
+/// Return whether a request failed because the blob does not exist.
+fn is_not_found(err: &(dyn Error + Send + Sync + 'static)) -> bool {
+    status_code(err) == Some(404)
 }
 
 /// Configuration for accessing a storage account and container.
 pub struct Config {
-    /// The storage account name.
-    pub storage_account: String,
-    /// The main storage account access key.
-    pub storage_account_key: String,
+    /// The credentials for the storage account.
+    pub credentials: Credentials,
     /// The container name;
     pub container: String,
 }
 
-/// Get the configuration for accessing the object storage from environment variables.
-pub fn config_from_env() -> Result<Config> {
-    Ok(Config {
-        storage_account: std::env::var(STORAGE_ACCOUNT)?,
-        storage_account_key: std::env::var(STORAGE_MASTER_KEY)?,
-        container: std::env::var(CONTAINER)?,
-    })
+/// Credentials for accessing a storage account.
+pub enum Credentials {
+    /// The storage account name and its main access key.
+    AccessKey {
+        /// The storage account name.
+        account: String,
+        /// The main storage account access key.
+        key: String,
+    },
+    /// The storage account name and a shared access signature token.
+    SasToken {
+        /// The storage account name.
+        account: String,
+        /// The shared access signature token.
+        token: String,
+    },
+    /// A connection string, containing the account, its credentials,
+    /// and optionally the endpoints of the services.
+    ConnectionString(String),
+    /// The well-known development account of a local storage emulator,
+    /// such as Azurite, reached at the given blob endpoint.
+    Emulator {
+        /// The blob endpoint of the emulator.
+        blob_endpoint: Url,
+    },
+}
+
+impl Config {
+    /// Get the configuration for accessing the object storage from environment variables.
+    ///
+    /// The credentials are read from the first of these that is set: the connection
+    /// string, the emulator endpoint, or the storage account with either its access
+    /// key or a shared access signature token.
+    pub fn from_env() -> Result<Self> {
+        let container = required_var(CONTAINER)?;
+
+        let credentials = if let Some(s) = optional_var(STORAGE_CONNECTION_STRING)? {
+            Credentials::ConnectionString(s)
+        } else if let Some(endpoint) = optional_var(STORAGE_ENDPOINT)? {
+            let blob_endpoint = Url::parse(&endpoint)
+                .map_err(|e| format!("invalid {} {:?}: {}", STORAGE_ENDPOINT, endpoint, e))?;
+            Credentials::Emulator { blob_endpoint }
+        } else {
+            let account = required_var(STORAGE_ACCOUNT).map_err(|_| {
+                format!(
+                    "missing storage credentials: set {}, {}, or {} with either {} or {}",
+                    STORAGE_CONNECTION_STRING,
+                    STORAGE_ENDPOINT,
+                    STORAGE_ACCOUNT,
+                    STORAGE_MASTER_KEY,
+                    STORAGE_SAS_TOKEN
+                )
+            })?;
+            match (
+                optional_var(STORAGE_MASTER_KEY)?,
+                optional_var(STORAGE_SAS_TOKEN)?,
+            ) {
+                (Some(key), _) => Credentials::AccessKey { account, key },
+                (None, Some(token)) => Credentials::SasToken { account, token },
+                (None, None) => {
+                    return Err(format!(
+                        "missing credentials for storage account {}: set {} or {}",
+                        account, STORAGE_MASTER_KEY, STORAGE_SAS_TOKEN
+                    )
+                    .into())
+                }
+            }
+        };
+
+        Ok(Self {
+            credentials,
+            container,
+        })
+    }
+}
+
+/// Create a client for the container of the configuration.
+pub fn container_client(
+    config: &Config,
+    http_client: Arc<Box<dyn HttpClient>>,
+) -> Result<Arc<ContainerClient>> {
+    let account = match &config.credentials {
+        Credentials::AccessKey { account, key } => {
+            StorageAccountClient::new_access_key(http_client, account, key)
+        }
+        Credentials::SasToken { account, token } => {
+            StorageAccountClient::new_sas_token(http_client, account, token)?
+        }
+        Credentials::ConnectionString(s) => {
+            StorageAccountClient::new_connection_string(http_client, s)?
+        }
+        // Only the blob service is used, so the other endpoints are never reached.
+        Credentials::Emulator { blob_endpoint } => StorageAccountClient::new_emulator(
+            http_client,
+            blob_endpoint,
+            blob_endpoint,
+            blob_endpoint,
+            blob_endpoint,
+        ),
+    };
+
+    Ok(account
+        .as_storage_client()
+        .as_container_client(&config.container))
+}
+
+/// Return the value of the given environment variable, failing if it is not set.
+fn required_var(name: &str) -> Result<String> {
+    optional_var(name)?.ok_or_else(|| format!("environment variable {} is not set", name).into())
+}
+
+/// Return the value of the given environment variable, if it is set and not empty.
+fn optional_var(name: &str) -> Result<Option<String>> {
+    match env::var(name) {
+        Ok(v) if v.is_empty() => Ok(None),
+        Ok(v) => Ok(Some(v)),
+        Err(env::VarError::NotPresent) => Ok(None),
+        Err(e) => Err(format!("invalid environment variable {}: {}", name, e).into()),
+    }
 }
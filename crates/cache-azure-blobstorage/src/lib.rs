@@ -1,31 +1,38 @@
-//! Impement the WASI cache interface using the Azure Blob Storage service.
+//! Implement the WASI cache interface using the Azure Blob Storage service.
 
 #![deny(missing_docs)]
 
 pub mod azure;
 
 use azure::Config;
-use azure_core::{HttpClient, WasiHttpClient};
+use azure_core::WasiHttpClient;
+use azure_storage::blob::prelude::ContainerClient;
 use futures::executor::block_on;
+use once_cell::sync::OnceCell;
 use std::sync::Arc;
 use wasi_cache::*;
 
 // The path to the cache interface is relative because this crate is in its own workspace.
 wit_bindgen_rust::export!("../../wit/ephemeral/wasi-cache.wit");
 
+/// The configuration, read from the environment the first time the cache is used.
+static CONFIG: OnceCell<Config> = OnceCell::new();
+
 /// Azure implementation for the cache interface.
-/// The implementation assumes the storage container, service account,
-/// and service account key have been passed in as environment variables.
+/// The implementation assumes the storage container and the credentials for
+/// the storage account have been passed in as environment variables.
 pub struct WasiCache {}
 
 impl wasi_cache::WasiCache for WasiCache {
     /// Set the payload for a given key as an Azure blob.
-    fn set(key: String, value: Payload, _: Option<u32>) -> Result<(), Error> {
-        Ok(block_on(set(key, value))?)
+    /// If provided, the time-to-live argument (in seconds) is stored as the
+    /// expiration time in the metadata of the blob.
+    fn set(key: String, value: Payload, ttl: Option<u32>) -> Result<(), Error> {
+        Ok(block_on(set(key, value, ttl))?)
     }
 
-    /// Read the payload for a given key from an Azure blob.
-    fn get(key: String) -> Result<Payload, Error> {
+    /// Read the payload for a given key from an Azure blob, if it has not expired.
+    fn get(key: String) -> Result<Option<Payload>, Error> {
         Ok(block_on(get(key))?)
     }
 
@@ -33,34 +40,117 @@ impl wasi_cache::WasiCache for WasiCache {
     fn delete(key: String) -> Result<(), Error> {
         Ok(block_on(delete(key))?)
     }
+
+    /// Read the payloads for the given keys from Azure blobs.
+    fn get_many(keys: Vec<String>) -> Result<Vec<Option<Payload>>, Error> {
+        keys.into_iter().map(Self::get).collect()
+    }
+
+    /// Set the payloads for the given keys as Azure blobs.
+    fn set_many(entries: Vec<(String, Payload)>, ttl: Option<u32>) -> Result<(), Error> {
+        for (key, value) in entries {
+            Self::set(key, value, ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the payloads for the given keys from Azure blobs.
+    fn delete_many(keys: Vec<String>) -> Result<(), Error> {
+        for key in keys {
+            Self::delete(key)?;
+        }
+        Ok(())
+    }
+
+    /// Atomic operations are not supported by this implementation.
+    fn increment(_: String, _: i64) -> Result<i64, Error> {
+        Err(Error::Other(
+            "increment is not supported by the Azure Blob Storage cache".to_string(),
+        ))
+    }
+
+    /// Atomic operations are not supported by this implementation.
+    fn compare_and_swap(_: String, _: Option<Payload>, _: Payload) -> Result<bool, Error> {
+        Err(Error::Other(
+            "compare-and-swap is not supported by the Azure Blob Storage cache".to_string(),
+        ))
+    }
+
+    /// List the names of the Azure blobs starting with the given prefix.
+    /// The cursor is the marker returned by the service.
+    fn list_keys(prefix: String, cursor: Option<String>, limit: u32) -> Result<KeyPage, Error> {
+        let (keys, cursor) = block_on(list(prefix, cursor, limit))?;
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the Azure blob for a given key, from the
+    /// expiration time in its metadata.
+    fn get_ttl(key: String) -> Result<Option<Lifetime>, Error> {
+        Ok(block_on(get_ttl(key))?)
+    }
 }
 
-async fn get(name: String) -> azure::Result<Vec<u8>> {
-    let (cfg, client) = cfg_client();
-    let bytes = azure::read_blob(name, &cfg, client).await?.to_vec();
-    Ok(bytes)
+async fn get(name: String) -> azure::Result<Option<Vec<u8>>> {
+    let blob = azure::read_blob(name, &container()?).await?;
+    Ok(blob.map(|b| b.data.to_vec()))
 }
 
-async fn set(name: String, bytes: Vec<u8>) -> azure::Result<()> {
-    let (cfg, client) = cfg_client();
-    let _ = azure::write_blob(name, bytes, &cfg, client).await?;
+async fn get_ttl(name: String) -> azure::Result<Option<Lifetime>> {
+    let blob = match azure::read_blob(name, &container()?).await? {
+        Some(b) => b,
+        None => return Ok(None),
+    };
+    Ok(Some(match blob.expires {
+        Some(expires) => {
+            let remaining = expires.saturating_sub(azure::now()?);
+            Lifetime::Remaining(remaining.min(u32::MAX as u64) as u32)
+        }
+        None => Lifetime::Persistent,
+    }))
+}
+
+async fn set(name: String, bytes: Vec<u8>, ttl: Option<u32>) -> azure::Result<()> {
+    let options = azure::WriteOptions {
+        expires: match ttl {
+            Some(s) => Some(azure::now()? + s as u64),
+            None => None,
+        },
+        ..Default::default()
+    };
+    let _ = azure::write_blob(name, bytes, &options, &container()?).await?;
     Ok(())
 }
 
 async fn delete(name: String) -> azure::Result<()> {
-    let (cfg, client) = cfg_client();
-    azure::delete_blob(name, &cfg, client).await?;
+    azure::delete_blob(name, &container()?).await?;
     Ok(())
 }
 
-fn cfg_client() -> (Config, Arc<Box<dyn HttpClient>>) {
-    let cfg =
-        azure::config_from_env().expect("cannot get storage configuration from the environment");
-    (cfg, Arc::new(Box::new(WasiHttpClient {})))
+async fn list(
+    prefix: String,
+    marker: Option<String>,
+    limit: u32,
+) -> azure::Result<(Vec<String>, Option<String>)> {
+    azure::list_blobs(prefix, marker, limit, &container()?).await
+}
+
+/// Return a client for the configured container, loading the configuration if needed.
+fn container() -> azure::Result<Arc<ContainerClient>> {
+    let config = CONFIG.get_or_try_init(Config::from_env)?;
+    azure::container_client(config, Arc::new(Box::new(WasiHttpClient {})))
 }
 
 impl From<Box<dyn std::error::Error + Send + Sync>> for wasi_cache::Error {
-    fn from(_: Box<dyn std::error::Error + Send + Sync>) -> Self {
-        Self::Error
+    fn from(e: Box<dyn std::error::Error + Send + Sync>) -> Self {
+        let msg = e.to_string();
+        match azure::status_code(e.as_ref()) {
+            Some(400) => Self::InvalidKey(msg),
+            Some(401) | Some(403) => Self::PermissionDenied(msg),
+            Some(404) => Self::NotFound(msg),
+            Some(408) => Self::Timeout(msg),
+            Some(413) => Self::ValueTooLarge(msg),
+            Some(500..=599) => Self::BackendUnavailable(msg),
+            _ => Self::Other(msg),
+        }
     }
 }
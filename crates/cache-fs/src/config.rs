@@ -0,0 +1,91 @@
+//! The optional configuration file of the filesystem cache, which contains
+//! one `name = value` setting per line:
+//!
+//! ```text
+//! max-bytes = 1048576
+//! max-entries = 1000
+//! sharding = false
+//! ```
+//!
+//! Empty lines and lines starting with `#` are ignored.
+
+use crate::{lock::Lock, wasi_cache::Error};
+use std::{
+    fs::{File, OpenOptions},
+    io::{ErrorKind, Read, Write},
+    path::Path,
+};
+
+/// The path of the configuration file.
+const CONFIG_PATH: &str = "cache/.config";
+
+/// Read the settings of the configuration file, in order, or none if it does not exist.
+pub(crate) fn read() -> Result<Vec<(String, String)>, anyhow::Error> {
+    let config = match read_to_string(Path::new(CONFIG_PATH))? {
+        Some(c) => c,
+        None => return Ok(Vec::new()),
+    };
+
+    let mut settings = Vec::new();
+    for line in config.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let (name, value) = line
+            .split_once('=')
+            .ok_or_else(|| anyhow::anyhow!("invalid configuration line {}", line))?;
+        settings.push((name.trim().to_string(), value.trim().to_string()));
+    }
+
+    Ok(settings)
+}
+
+/// Return the value of the given setting, if it is set. Since the file is
+/// read in order, the last value of a setting that is set more than once wins.
+pub(crate) fn get(name: &str) -> Result<Option<String>, anyhow::Error> {
+    Ok(read()?
+        .into_iter()
+        .rev()
+        .find(|(n, _)| n == name)
+        .map(|(_, v)| v))
+}
+
+/// Set the given setting, unless it is already set, while holding the lock
+/// of the configuration file, and return its value.
+pub(crate) fn get_or_set(name: &str, value: &str) -> Result<String, Error> {
+    let _lock = Lock::acquire(Path::new(CONFIG_PATH))?;
+    if let Some(v) = get(name)? {
+        return Ok(v);
+    }
+
+    // The setting is added on its own line, even if the file was edited
+    // and does not end with a line break.
+    let contents = read_to_string(Path::new(CONFIG_PATH))?.unwrap_or_default();
+    let separator = if contents.is_empty() || contents.ends_with('\n') {
+        ""
+    } else {
+        "\n"
+    };
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(CONFIG_PATH)?;
+    writeln!(file, "{}{} = {}", separator, name, value)?;
+    file.sync_all()?;
+    Ok(value.to_string())
+}
+
+/// Read the contents of a file, if it exists.
+pub(crate) fn read_to_string(path: &Path) -> Result<Option<String>, anyhow::Error> {
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut buf = String::new();
+    file.read_to_string(&mut buf)?;
+    Ok(Some(buf))
+}
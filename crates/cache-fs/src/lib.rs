@@ -2,61 +2,591 @@
 
 #![deny(missing_docs)]
 
+mod config;
+mod lock;
+mod lru;
+
+use lock::Lock;
+use lru::{Index, Limits};
+use sha2::{Digest, Sha256};
 use std::{
+    collections::hash_map::RandomState,
     fs::File,
-    io::{Read, Write},
-    path::PathBuf,
+    hash::{BuildHasher, Hasher},
+    io::{ErrorKind, Read, Write},
+    path::{Path, PathBuf},
+    time::{SystemTime, UNIX_EPOCH},
 };
 use wasi_cache::*;
 wit_bindgen_rust::export!("../../wit/ephemeral/wasi-cache.wit");
 
+/// Environment variable that, when set to `true`, distributes the entries
+/// across 256 subdirectories, based on the digest of their key.
+/// The layout is recorded in the configuration file of the cache when it is
+/// first used, and instances requesting a different layout fail.
+const SHARDING_ENV: &str = "CACHE_FS_SHARDING";
+
+/// The maximum length of an encoded key that is used as a file name.
+/// Keys with longer encodings are stored using the digest of the key.
+const MAX_NAME_LEN: usize = 200;
+
+/// The maximum length of a key, in bytes, which bounds the memory allocated
+/// when reading the key from the header of a file.
+const MAX_KEY_LEN: usize = 64 * 1024;
+
 /// The WASI filesystem implementation for the cache interface.
 struct WasiCache {}
 
 impl wasi_cache::WasiCache for WasiCache {
     /// Set the payload for the given key.
-    /// This implementation ignores the time-to-live argument, as it stores the
-    /// payloads as files.
-    fn set(key: String, payload: Payload, _: Option<u32>) -> Result<(), Error> {
-        let mut file = File::create(path(&key)?)?;
-        file.write_all(&payload)?;
-        Ok(())
+    /// If provided, the time-to-live argument (in seconds) is used to compute
+    /// the expiration time, which is stored in the header of the file.
+    fn set(key: String, payload: Payload, ttl: Option<u32>) -> Result<(), Error> {
+        let expires = match ttl {
+            Some(s) => now()? + s as u64,
+            None => 0,
+        };
+
+        let path = path(&key)?;
+        let _lock = Lock::acquire(&path)?;
+        write_entry(
+            &path,
+            Entry {
+                key,
+                expires,
+                payload,
+            },
+        )
     }
 
     /// Get the payload stored in the cache for the given key.
-    fn get(key: String) -> Result<Payload, Error> {
-        let mut file = match File::open(path(&key)?) {
-            Ok(f) => f,
-            Err(_) => return Ok(Vec::new()),
+    /// Expired entries are treated as missing, and their file is removed.
+    /// If the cache is bounded, the modification time of the file is updated,
+    /// which records the access without rewriting the index.
+    fn get(key: String) -> Result<Option<Payload>, Error> {
+        let path = path(&key)?;
+        let entry = match read_entry(&path)? {
+            Some(e) if e.key == key => e,
+            _ => return Ok(None),
         };
+        if entry.is_expired()? {
+            // Another instance may have replaced the entry since it was read,
+            // so it is only removed if it is still expired once locked.
+            let _lock = Lock::acquire(&path)?;
+            return Ok(read_live_entry(&path, &key)?.map(|e| e.payload));
+        }
 
-        let mut buf = Vec::new();
-        file.read_to_end(&mut buf)?;
-        Ok(buf)
+        if Limits::load()?.is_bounded() {
+            touch(&path);
+        }
+        Ok(Some(entry.payload))
     }
 
     /// Remove the file for the given key.
+    /// Deleting a key that does not exist is not an error.
     fn delete(key: String) -> Result<(), Error> {
-        std::fs::remove_file(path(&key)?)?;
+        let path = path(&key)?;
+        let _lock = Lock::acquire(&path)?;
+        remove(&path)?;
+        update_index(&Limits::load()?, |index| index.remove(&path))?;
+        Ok(())
+    }
+
+    /// Get the payloads stored in the cache for the given keys.
+    fn get_many(keys: Vec<String>) -> Result<Vec<Option<Payload>>, Error> {
+        keys.into_iter().map(Self::get).collect()
+    }
+
+    /// Set the payloads for the given keys.
+    fn set_many(entries: Vec<(String, Payload)>, ttl: Option<u32>) -> Result<(), Error> {
+        for (key, payload) in entries {
+            Self::set(key, payload, ttl)?;
+        }
+        Ok(())
+    }
+
+    /// Remove the files for the given keys.
+    fn delete_many(keys: Vec<String>) -> Result<(), Error> {
+        for key in keys {
+            Self::delete(key)?;
+        }
+        Ok(())
+    }
+
+    /// Increment the integer stored for the given key while holding its lock.
+    /// The expiration time of an existing entry is preserved.
+    fn increment(key: String, delta: i64) -> Result<i64, Error> {
+        let path = path(&key)?;
+        let _lock = Lock::acquire(&path)?;
+        let (current, expires) = match read_live_entry(&path, &key)? {
+            Some(e) => (parse_integer(&e.payload)?, e.expires),
+            None => (0, 0),
+        };
+
+        let value = current
+            .checked_add(delta)
+            .ok_or_else(|| Error::Other(format!("increment of key {} overflows", key)))?;
+        write_entry(
+            &path,
+            Entry {
+                key,
+                expires,
+                payload: value.to_string().into_bytes(),
+            },
+        )?;
+        Ok(value)
+    }
+
+    /// Replace the payload for the given key while holding its lock,
+    /// if the current payload is equal to the old one.
+    fn compare_and_swap(key: String, old: Option<Payload>, value: Payload) -> Result<bool, Error> {
+        let path = path(&key)?;
+        let _lock = Lock::acquire(&path)?;
+        let current = read_live_entry(&path, &key)?.map(|e| e.payload);
+        if current != old {
+            return Ok(false);
+        }
+
+        write_entry(
+            &path,
+            Entry {
+                key,
+                expires: 0,
+                payload: value,
+            },
+        )?;
+        Ok(true)
+    }
+
+    /// List the keys starting with the given prefix, in lexicographic order.
+    /// The cursor of a page is the last key in that page. Since the whole
+    /// directory is scanned for every page, expired entries are skipped
+    /// without being removed.
+    fn list_keys(prefix: String, cursor: Option<String>, limit: u32) -> Result<KeyPage, Error> {
+        let mut keys = Vec::new();
+        for path in entry_paths(Path::new("cache"))? {
+            let (key, expires) = match read_header(&path)? {
+                Some(h) => h,
+                None => continue,
+            };
+            let after_cursor = match &cursor {
+                Some(c) => key > *c,
+                None => true,
+            };
+            if key.starts_with(&prefix) && after_cursor && (expires == 0 || expires > now()?) {
+                keys.push(key);
+            }
+        }
+        keys.sort();
+
+        let limit = limit.max(1) as usize;
+        let more = keys.len() > limit;
+        keys.truncate(limit);
+        let cursor = if more { keys.last().cloned() } else { None };
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, from the
+    /// expiration time stored in the header of the file.
+    fn get_ttl(key: String) -> Result<Option<Lifetime>, Error> {
+        let (stored, expires) = match read_header(&path(&key)?)? {
+            Some(h) => h,
+            None => return Ok(None),
+        };
+        if stored != key {
+            return Ok(None);
+        }
+
+        let now = now()?;
+        Ok(match expires {
+            0 => Some(Lifetime::Persistent),
+            e if e > now => Some(Lifetime::Remaining((e - now).min(u32::MAX as u64) as u32)),
+            _ => None,
+        })
+    }
+}
+
+/// A cache entry, as stored in a file.
+///
+/// The file starts with the expiration time, as seconds since the Unix epoch
+/// (or zero if the entry never expires), followed by the length of the key,
+/// the key, and the payload. All integers are little-endian.
+struct Entry {
+    /// The key of the entry.
+    key: String,
+    /// The expiration time of the entry.
+    expires: u64,
+    /// The payload of the entry.
+    payload: Vec<u8>,
+}
+
+impl Entry {
+    /// Check whether the entry has expired.
+    fn is_expired(&self) -> Result<bool, anyhow::Error> {
+        Ok(self.expires != 0 && self.expires <= now()?)
+    }
+
+    /// Return the size of the on-disk representation of the entry.
+    fn len(&self) -> u64 {
+        (12 + self.key.len() + self.payload.len()) as u64
+    }
+
+    /// Check whether the key of the entry is short enough to be read back.
+    fn check_key(&self) -> Result<(), Error> {
+        if self.key.len() > MAX_KEY_LEN {
+            return Err(Error::InvalidKey(format!(
+                "key of {} bytes exceeds the maximum key length of {} bytes",
+                self.key.len(),
+                MAX_KEY_LEN
+            )));
+        }
         Ok(())
     }
+
+    /// Encode the entry into its on-disk representation.
+    fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(self.len() as usize);
+        buf.extend_from_slice(&self.expires.to_le_bytes());
+        buf.extend_from_slice(&(self.key.len() as u32).to_le_bytes());
+        buf.extend_from_slice(self.key.as_bytes());
+        buf.extend_from_slice(&self.payload);
+        buf
+    }
+
+    /// Decode an entry from its on-disk representation.
+    fn decode(mut buf: Vec<u8>) -> Result<Self, anyhow::Error> {
+        if buf.len() < 12 {
+            anyhow::bail!("invalid cache entry header");
+        }
+
+        let mut expires = [0; 8];
+        expires.copy_from_slice(&buf[..8]);
+        let mut key_len = [0; 4];
+        key_len.copy_from_slice(&buf[8..12]);
+
+        let key_end = 12 + u32::from_le_bytes(key_len) as usize;
+        if buf.len() < key_end {
+            anyhow::bail!("invalid cache entry key");
+        }
+
+        let payload = buf.split_off(key_end);
+        let key = String::from_utf8(buf.split_off(12))?;
+        Ok(Self {
+            key,
+            expires: u64::from_le_bytes(expires),
+            payload,
+        })
+    }
+}
+
+/// Read the entry for the given key, if it exists and has not expired.
+/// The file of an expired entry is removed, so the caller is expected to
+/// hold the lock for the entry.
+fn read_live_entry(path: &Path, key: &str) -> Result<Option<Entry>, Error> {
+    let entry = match read_entry(path)? {
+        Some(e) if e.key == key => e,
+        _ => return Ok(None),
+    };
+
+    if entry.is_expired()? {
+        if remove_expired(path)? {
+            update_index(&Limits::load()?, |index| index.remove(path))?;
+        }
+        return Ok(None);
+    }
+
+    Ok(Some(entry))
+}
+
+/// Write the entry to the given file, and update the index of the cache.
+/// The caller is expected to hold the lock for the entry.
+fn write_entry(path: &Path, entry: Entry) -> Result<(), Error> {
+    entry.check_key()?;
+    let limits = Limits::load()?;
+    if let Some(max) = limits.max_bytes {
+        if entry.len() > max {
+            return Err(Error::ValueTooLarge(format!(
+                "entry of {} bytes exceeds the maximum cache size of {} bytes",
+                entry.len(),
+                max
+            )));
+        }
+    }
+
+    write_atomic(path, &entry.encode())?;
+    update_index(&limits, |index| index.touch(path, entry.len()))?;
+    Ok(())
+}
+
+/// Parse a payload containing a decimal integer, like `parse_integer` of the
+/// host bindings, which this guest module cannot depend on.
+fn parse_integer(payload: &[u8]) -> Result<i64, Error> {
+    std::str::from_utf8(payload)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| Error::Other("value is not an integer".to_string()))
+}
+
+/// Read the entry stored in the given file, if it exists.
+fn read_entry(path: &Path) -> Result<Option<Entry>, anyhow::Error> {
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut buf = Vec::new();
+    file.read_to_end(&mut buf)?;
+    Ok(Some(Entry::decode(buf)?))
+}
+
+/// Read the key and the expiration time stored in the header of the given
+/// file, if it exists.
+fn read_header(path: &Path) -> Result<Option<(String, u64)>, anyhow::Error> {
+    let mut file = match File::open(path) {
+        Ok(f) => f,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut expires = [0; 8];
+    file.read_exact(&mut expires)?;
+    let mut key_len = [0; 4];
+    file.read_exact(&mut key_len)?;
+    let key_len = u32::from_le_bytes(key_len) as usize;
+    if key_len > MAX_KEY_LEN {
+        anyhow::bail!("invalid cache entry key");
+    }
+    let mut key = vec![0; key_len];
+    file.read_exact(&mut key)?;
+    Ok(Some((String::from_utf8(key)?, u64::from_le_bytes(expires))))
+}
+
+/// Return the paths of the entry files in the given directory and its shard
+/// subdirectories. Files starting with `.`, such as the index, the
+/// configuration, locks, and temporary files, are not entries.
+fn entry_paths(dir: &Path) -> Result<Vec<PathBuf>, std::io::Error> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(e) => e,
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e),
+    };
+
+    let mut paths = Vec::new();
+    for entry in entries {
+        let entry = entry?;
+        if entry.file_name().to_string_lossy().starts_with('.') {
+            continue;
+        }
+        if entry.file_type()?.is_dir() {
+            paths.extend(entry_paths(&entry.path())?);
+        } else {
+            paths.push(entry.path());
+        }
+    }
+
+    Ok(paths)
+}
+
+/// Update the index of the cache, then evict the least recently used entries
+/// that exceed the limits. This does nothing if the cache is unbounded.
+///
+/// The lock of the index is held for the whole update. Since the lock of an
+/// entry is acquired before the lock of the index, entries whose lock is held,
+/// such as the ones being written, are not waited for, and are evicted by a
+/// later update instead.
+fn update_index(limits: &Limits, update: impl FnOnce(&mut Index)) -> Result<(), Error> {
+    if !limits.is_bounded() {
+        return Ok(());
+    }
+
+    let _lock = Index::lock()?;
+    let mut index = Index::load()?;
+    update(&mut index);
+    index.evict(limits, |path| match Lock::try_acquire(path)? {
+        Some(_lock) => {
+            remove(path)?;
+            Ok(true)
+        }
+        None => Ok(false),
+    })?;
+
+    Ok(index.save()?)
+}
+
+/// Write the contents of a file atomically.
+///
+/// The contents are first written to a temporary file in the same directory,
+/// which is then renamed to the destination, so concurrent readers never
+/// observe a partially written file.
+fn write_atomic(path: &Path, buf: &[u8]) -> Result<(), anyhow::Error> {
+    let tmp = hidden_path(path, "tmp")?;
+
+    let res = File::create(&tmp).and_then(|mut file| {
+        file.write_all(buf)?;
+        file.sync_all()?;
+        std::fs::rename(&tmp, path)
+    });
+    if res.is_err() {
+        let _ = std::fs::remove_file(&tmp);
+    }
+
+    Ok(res?)
+}
+
+/// Remove the file of an expired entry, and return whether it was removed.
+///
+/// Even though the caller holds the lock of the entry, the lock may have been
+/// broken while another instance was slowly rewriting the entry. The file is
+/// therefore first renamed to a unique name, and only removed if it still
+/// contains an expired entry. Otherwise, the entry was rewritten since it was
+/// read, and the file is put back, unless the entry was written yet again in
+/// between, since hard links never replace a file.
+fn remove_expired(path: &Path) -> Result<bool, anyhow::Error> {
+    let removed = hidden_path(path, "expired")?;
+    match std::fs::rename(path, &removed) {
+        Ok(()) => {}
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+        Err(e) => return Err(e.into()),
+    }
+
+    let expired = match read_entry(&removed) {
+        Ok(Some(e)) => e.is_expired()?,
+        _ => true,
+    };
+    if !expired {
+        let _ = std::fs::hard_link(&removed, path);
+    }
+    remove(&removed)?;
+    Ok(expired)
+}
+
+/// Return a unique path, in the directory of the given file, for a hidden file
+/// derived from it, such as a temporary file, with the given extension.
+fn hidden_path(path: &Path, extension: &str) -> Result<PathBuf, anyhow::Error> {
+    let name = path
+        .file_name()
+        .ok_or_else(|| anyhow::anyhow!("invalid cache path {:?}", path))?
+        .to_string_lossy();
+    // `RandomState` is seeded from the random source of the runtime, which
+    // avoids collisions between instances sharing the same directory.
+    let suffix = RandomState::new().build_hasher().finish();
+    Ok(path.with_file_name(format!(".{}.{:016x}.{}", name, suffix, extension)))
+}
+
+/// Set the modification time of a file to the current time, which records
+/// an access to the entry it stores. Failures are ignored, since the access
+/// time is only used to choose the entries to evict.
+fn touch(path: &Path) {
+    let _ = File::options()
+        .write(true)
+        .open(path)
+        .and_then(|f| f.set_modified(SystemTime::now()));
+}
+
+/// Remove a file, ignoring the error if it does not exist.
+fn remove(path: &Path) -> Result<(), std::io::Error> {
+    match std::fs::remove_file(path) {
+        Err(e) if e.kind() != ErrorKind::NotFound => Err(e),
+        _ => Ok(()),
+    }
+}
+
+/// Return the path for the file corresponding to the given key.
+///
+/// When sharding is enabled, the file is placed in a subdirectory named
+/// after the first byte of the digest of the key.
+fn path(key: &str) -> Result<PathBuf, Error> {
+    let mut path = PathBuf::from("cache");
+    if sharding()? {
+        path.push(format!("{:02x}", Sha256::digest(key.as_bytes())[0]));
+    }
+    path.push(file_name(key));
+    Ok(path)
+}
+
+/// Encode the key into a flat file name.
+///
+/// ASCII lowercase letters, digits, `-`, and `_` are kept as they are, and all
+/// other bytes are percent-encoded using uppercase hex digits. This makes the
+/// encoding reversible, and collision-free even on case-insensitive filesystems.
+/// Empty keys and keys whose encoding is too long use the hex digest of the key
+/// prefixed with `#`, which the percent-encoding never produces. Since the key
+/// is also stored in the file, lookups can still verify it.
+fn file_name(key: &str) -> String {
+    let mut name = String::with_capacity(key.len());
+    for b in key.bytes() {
+        match b {
+            b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' => name.push(b as char),
+            _ => name.push_str(&format!("%{:02X}", b)),
+        }
+    }
+
+    if name.is_empty() || name.len() > MAX_NAME_LEN {
+        name = Sha256::digest(key.as_bytes())
+            .iter()
+            .fold(String::from("#"), |mut acc, b| {
+                acc.push_str(&format!("{:02x}", b));
+                acc
+            });
+    }
+
+    name
+}
+
+/// Check whether the entries are sharded into subdirectories.
+///
+/// Since entries stored with one layout cannot be found with the other, the
+/// layout is recorded in the configuration file when the cache is first used,
+/// and is then used by every instance. Instances explicitly requesting the
+/// other layout fail, instead of silently missing the existing entries.
+fn sharding() -> Result<bool, Error> {
+    let requested = std::env::var(SHARDING_ENV).ok().map(|v| v == "true");
+    let recorded = match config::get("sharding")? {
+        Some(v) => v,
+        None => config::get_or_set("sharding", &requested.unwrap_or(false).to_string())?,
+    };
+
+    let sharding = match recorded.as_str() {
+        "true" => true,
+        "false" => false,
+        other => {
+            return Err(Error::Other(format!(
+                "invalid sharding configuration {}",
+                other
+            )))
+        }
+    };
+    match requested {
+        Some(r) if r != sharding => Err(Error::Other(format!(
+            "{} is {}, but the cache was created with sharding = {}",
+            SHARDING_ENV, r, sharding
+        ))),
+        _ => Ok(sharding),
+    }
 }
 
-/// Return the absolute path for the file corresponding to the given key.
-fn path(name: &str) -> Result<PathBuf, anyhow::Error> {
-    Ok(PathBuf::from("cache").join(name))
+/// Return the current time as seconds since the Unix epoch.
+fn now() -> Result<u64, anyhow::Error> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
 }
 
-// TODO
-// Error handling is currently not implemented.
 impl From<anyhow::Error> for Error {
-    fn from(_: anyhow::Error) -> Self {
-        Self::Error
+    fn from(e: anyhow::Error) -> Self {
+        match e.downcast::<std::io::Error>() {
+            Ok(e) => e.into(),
+            Err(e) => Self::Other(format!("{:#}", e)),
+        }
     }
 }
 
 impl From<std::io::Error> for Error {
-    fn from(_: std::io::Error) -> Self {
-        Self::Error
+    fn from(e: std::io::Error) -> Self {
+        let msg = e.to_string();
+        match e.kind() {
+            ErrorKind::PermissionDenied => Self::PermissionDenied(msg),
+            ErrorKind::TimedOut => Self::Timeout(msg),
+            _ => Self::Other(msg),
+        }
     }
 }
@@ -0,0 +1,120 @@
+//! Exclusive locks for the entries of the filesystem cache.
+//!
+//! A lock is a file created next to the entry it protects, or next to the
+//! index of the cache, which is locked while it is updated. Creating the file
+//! fails if it already exists, which makes acquiring the lock atomic, even for
+//! instances sharing the same directory. Locks left behind by an instance that
+//! crashed are broken once they are older than `STALE_AFTER`. A stale lock is
+//! broken by renaming it to a unique name, which only one instance can do, so
+//! instances that found it stale at the same time cannot all acquire the lock.
+
+use crate::wasi_cache::Error;
+use std::{
+    collections::hash_map::RandomState,
+    fs::OpenOptions,
+    hash::{BuildHasher, Hasher},
+    io::ErrorKind,
+    path::{Path, PathBuf},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// The maximum time to wait for a lock.
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// The time to wait between two attempts to acquire a lock.
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The age after which a lock is considered abandoned.
+const STALE_AFTER: Duration = Duration::from_secs(30);
+
+/// An exclusive lock on a cache entry, released when dropped.
+pub(crate) struct Lock {
+    /// The path of the lock file.
+    path: PathBuf,
+}
+
+impl Lock {
+    /// Acquire the lock for the entry stored at the given path.
+    pub fn acquire(entry: &Path) -> Result<Self, Error> {
+        let start = Instant::now();
+        loop {
+            if let Some(lock) = Self::try_acquire(entry)? {
+                return Ok(lock);
+            }
+
+            if start.elapsed() > TIMEOUT {
+                return Err(Error::Timeout(format!(
+                    "timed out waiting for the lock of {}",
+                    entry.display()
+                )));
+            }
+
+            thread::sleep(RETRY_INTERVAL);
+        }
+    }
+
+    /// Acquire the lock for the entry stored at the given path, if it is not
+    /// already held, without waiting for it.
+    pub fn try_acquire(entry: &Path) -> Result<Option<Self>, Error> {
+        let name = entry
+            .file_name()
+            .ok_or_else(|| Error::InvalidKey(format!("invalid cache path {:?}", entry)))?
+            .to_string_lossy();
+        let path = entry.with_file_name(format!(".{}.lock", name));
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir)?;
+        }
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(_) => return Ok(Some(Self { path })),
+                Err(e) if e.kind() == ErrorKind::AlreadyExists => {}
+                Err(e) => return Err(e.into()),
+            }
+
+            if !is_stale(&path) {
+                return Ok(None);
+            }
+            break_stale(&path);
+        }
+    }
+}
+
+impl Drop for Lock {
+    fn drop(&mut self) {
+        let _ = std::fs::remove_file(&self.path);
+    }
+}
+
+/// Break the stale lock at the given path.
+///
+/// The lock is first renamed to a unique name, then removed if it is still
+/// stale. Otherwise, another instance broke the stale lock and acquired it
+/// since it was found stale, so the lock is put back, unless the lock was
+/// acquired again in between, since hard links never replace a file.
+fn break_stale(path: &Path) {
+    // `RandomState` is seeded from the random source of the runtime, which
+    // avoids collisions between instances sharing the same directory.
+    let suffix = RandomState::new().build_hasher().finish();
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let broken = path.with_file_name(format!("{}.{:016x}.stale", name, suffix));
+    if std::fs::rename(path, &broken).is_err() {
+        // Another instance already broke the lock, or it was released.
+        return;
+    }
+
+    if !is_stale(&broken) {
+        let _ = std::fs::hard_link(&broken, path);
+    }
+    let _ = std::fs::remove_file(&broken);
+}
+
+/// Check whether the lock file at the given path was abandoned.
+fn is_stale(path: &Path) -> bool {
+    let age = std::fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok());
+    matches!(age, Some(age) if age > STALE_AFTER)
+}
@@ -0,0 +1,210 @@
+//! Size-bounded, least recently used eviction for the filesystem cache.
+//!
+//! The limits are read from the `CACHE_FS_MAX_BYTES` and `CACHE_FS_MAX_ENTRIES`
+//! environment variables, or from the `max-bytes` and `max-entries` settings
+//! of the configuration file of the cache.
+//!
+//! Environment variables take precedence over the configuration file.
+//! When no limit is configured, the cache is unbounded and no index is kept.
+//!
+//! The index records the size and the order of the writes of every entry.
+//! It is rewritten atomically after every write and deletion, while holding
+//! its lock, so instances sharing the same directory never overwrite each
+//! other's updates. Reads do not rewrite the index, and update the modification
+//! time of the file of the entry instead, which is used to find the least
+//! recently used entries, with the order of the writes breaking ties.
+
+use crate::{
+    config::{self, read_to_string},
+    lock::Lock,
+    wasi_cache::Error,
+};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+/// Environment variable for the maximum total size of the entries, in bytes.
+const MAX_BYTES_ENV: &str = "CACHE_FS_MAX_BYTES";
+
+/// Environment variable for the maximum number of entries.
+const MAX_ENTRIES_ENV: &str = "CACHE_FS_MAX_ENTRIES";
+
+/// The path of the index file.
+const INDEX_PATH: &str = "cache/.index";
+
+/// The limits of the cache.
+#[derive(Default)]
+pub(crate) struct Limits {
+    /// The maximum total size of the entries, in bytes.
+    pub max_bytes: Option<u64>,
+    /// The maximum number of entries.
+    pub max_entries: Option<u64>,
+}
+
+impl Limits {
+    /// Load the limits from the configuration file and the environment.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let mut limits = Self::default();
+        for (name, value) in config::read()? {
+            match name.as_str() {
+                "max-bytes" => limits.max_bytes = Some(value.parse()?),
+                "max-entries" => limits.max_entries = Some(value.parse()?),
+                // The layout of the entries, which is read when computing their path.
+                "sharding" => {}
+                other => anyhow::bail!("unknown configuration key {}", other),
+            }
+        }
+
+        if let Ok(v) = std::env::var(MAX_BYTES_ENV) {
+            limits.max_bytes = Some(v.parse()?);
+        }
+        if let Ok(v) = std::env::var(MAX_ENTRIES_ENV) {
+            limits.max_entries = Some(v.parse()?);
+        }
+
+        Ok(limits)
+    }
+
+    /// Check whether any limit is configured.
+    pub fn is_bounded(&self) -> bool {
+        self.max_bytes.is_some() || self.max_entries.is_some()
+    }
+
+    /// Check whether the given total size and number of entries are within the limits.
+    fn allow(&self, bytes: u64, entries: usize) -> bool {
+        let over_bytes = matches!(self.max_bytes, Some(max) if bytes > max);
+        let over_entries = matches!(self.max_entries, Some(max) if entries as u64 > max);
+        !over_bytes && !over_entries
+    }
+}
+
+/// The usage of a single entry.
+struct Usage {
+    /// The logical time of the last write.
+    tick: u64,
+    /// The size of the file, in bytes.
+    size: u64,
+}
+
+/// The index of the entries stored in the cache.
+///
+/// The file contains the current logical time on the first line, followed by
+/// one line for every entry with its last write, its size, and its path.
+#[derive(Default)]
+pub(crate) struct Index {
+    /// The logical time, incremented on every write.
+    clock: u64,
+    /// The usage of the entries, by path.
+    entries: HashMap<PathBuf, Usage>,
+}
+
+impl Index {
+    /// Acquire the lock of the index, which must be held from loading the
+    /// index until saving it.
+    pub fn lock() -> Result<Lock, Error> {
+        Lock::acquire(Path::new(INDEX_PATH))
+    }
+
+    /// Load the index, or return an empty index if it does not exist.
+    pub fn load() -> Result<Self, anyhow::Error> {
+        let mut index = Self::default();
+        let contents = match read_to_string(Path::new(INDEX_PATH))? {
+            Some(c) => c,
+            None => return Ok(index),
+        };
+
+        let mut lines = contents.lines();
+        if let Some(clock) = lines.next() {
+            index.clock = clock.parse()?;
+        }
+        for line in lines {
+            let mut parts = line.splitn(3, ' ');
+            match (parts.next(), parts.next(), parts.next()) {
+                (Some(tick), Some(size), Some(path)) => {
+                    let usage = Usage {
+                        tick: tick.parse()?,
+                        size: size.parse()?,
+                    };
+                    index.entries.insert(PathBuf::from(path), usage);
+                }
+                _ => anyhow::bail!("invalid index line {}", line),
+            }
+        }
+
+        Ok(index)
+    }
+
+    /// Write the index.
+    pub fn save(&self) -> Result<(), anyhow::Error> {
+        let mut contents = format!("{}\n", self.clock);
+        for (path, usage) in &self.entries {
+            contents.push_str(&format!(
+                "{} {} {}\n",
+                usage.tick,
+                usage.size,
+                path.display()
+            ));
+        }
+
+        crate::write_atomic(Path::new(INDEX_PATH), contents.as_bytes())
+    }
+
+    /// Record a write of the entry stored at the given path.
+    pub fn touch(&mut self, path: &Path, size: u64) {
+        self.clock += 1;
+        let usage = Usage {
+            tick: self.clock,
+            size,
+        };
+        self.entries.insert(path.to_path_buf(), usage);
+    }
+
+    /// Remove the entry stored at the given path.
+    pub fn remove(&mut self, path: &Path) {
+        self.entries.remove(path);
+    }
+
+    /// Remove the least recently used entries until the index is within the
+    /// given limits, using `remove` to remove their files. Entries for which
+    /// `remove` returns `false`, such as the ones being written, are kept,
+    /// and evicted by a later update instead.
+    pub fn evict(
+        &mut self,
+        limits: &Limits,
+        mut remove: impl FnMut(&Path) -> Result<bool, Error>,
+    ) -> Result<(), Error> {
+        let mut bytes: u64 = self.entries.values().map(|u| u.size).sum();
+        if limits.allow(bytes, self.entries.len()) {
+            return Ok(());
+        }
+
+        // Entries whose file no longer exists have no modification time,
+        // and are removed first.
+        let mut by_age: Vec<(Option<SystemTime>, u64, PathBuf)> = self
+            .entries
+            .iter()
+            .map(|(path, usage)| (modified(path), usage.tick, path.clone()))
+            .collect();
+        by_age.sort();
+
+        for (_, _, path) in by_age {
+            if limits.allow(bytes, self.entries.len()) {
+                break;
+            }
+            if remove(&path)? {
+                if let Some(usage) = self.entries.remove(&path) {
+                    bytes -= usage.size;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Return the modification time of a file, if it exists.
+fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
@@ -0,0 +1,240 @@
+//! Implement the WASI cache interface using an in-memory store.
+//! This is using a Wasmtime host implementation.
+
+mod snapshot;
+mod store;
+
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+use store::{Limits, Store};
+use wasi_cache::*;
+
+use cache_wasi_wasmtime::parse_integer;
+pub use cache_wasi_wasmtime::wasi_cache::{self, add_to_linker};
+
+/// In-memory implementation for the WASI cache interface.
+#[derive(Clone)]
+pub struct MemoryCache {
+    /// The entries of the cache, shared by all clones of the cache.
+    store: Arc<Mutex<Store>>,
+}
+
+/// Builder for an in-memory cache.
+#[derive(Default)]
+pub struct MemoryCacheBuilder {
+    limits: Limits,
+    snapshot: Option<PathBuf>,
+}
+
+impl MemoryCacheBuilder {
+    /// Set the maximum number of entries in the cache.
+    pub fn max_entries(mut self, max: usize) -> Self {
+        self.limits.max_entries = Some(max);
+        self
+    }
+
+    /// Set the maximum total size (in bytes) of the keys and payloads in the cache.
+    pub fn max_bytes(mut self, max: usize) -> Self {
+        self.limits.max_bytes = Some(max);
+        self
+    }
+
+    /// Set the file the cache is loaded from when it is created, and saved
+    /// to when its last clone is dropped.
+    pub fn snapshot(mut self, path: impl AsRef<Path>) -> Self {
+        self.snapshot = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Create the cache, loading the snapshot if one is set and exists.
+    pub fn build(self) -> anyhow::Result<MemoryCache> {
+        let mut store = Store::new(self.limits);
+        if let Some(path) = &self.snapshot {
+            for e in snapshot::load(path)? {
+                // Entries that no longer fit the limits are evicted as they are loaded.
+                if let Err(err) = store.insert(&e.key, e.value, e.expires) {
+                    log::warn!("skipping snapshot entry {}: {:?}", e.key, err);
+                }
+            }
+        }
+        store.snapshot = self.snapshot;
+
+        Ok(MemoryCache {
+            store: Arc::new(Mutex::new(store)),
+        })
+    }
+}
+
+impl wasi_cache::WasiCache for MemoryCache {
+    /// Set the payload for the given key.
+    /// If provided, the time-to-live argument (in seconds) will be used to set the expiration time.
+    fn set(&mut self, key: &str, value: PayloadParam<'_>, ttl: Option<u32>) -> Result<(), Error> {
+        log::info!("setting key {}", key);
+        self.store().insert(key, value.to_vec(), expires(ttl))
+    }
+
+    /// Get the payload for the given key, if it exists.
+    fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
+        log::info!("getting key {}", key);
+        let now = SystemTime::now();
+        Ok(self.store().get(key, now).map(|e| e.value.clone()))
+    }
+
+    /// Delete the entry for the given key.
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        log::info!("deleting key {}", key);
+        self.store().remove(key);
+        Ok(())
+    }
+
+    /// Get the payloads for the given keys.
+    fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        let now = SystemTime::now();
+        let mut store = self.store();
+        Ok(keys
+            .iter()
+            .map(|k| store.get(k, now).map(|e| e.value.clone()))
+            .collect())
+    }
+
+    /// Set the payloads for the given keys.
+    fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        let expires = expires(ttl);
+        let mut store = self.store();
+        for (key, value) in entries {
+            store.insert(key, value.to_vec(), expires)?;
+        }
+        Ok(())
+    }
+
+    /// Delete the entries for the given keys.
+    fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        let mut store = self.store();
+        for key in keys {
+            store.remove(key);
+        }
+        Ok(())
+    }
+
+    /// Increment the integer stored for the given key.
+    /// The expiration time of an existing entry is preserved.
+    fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        let mut store = self.store();
+        let (current, expires) = match store.get(key, SystemTime::now()) {
+            Some(e) => (parse_integer(&e.value)?, e.expires),
+            None => (0, None),
+        };
+
+        let value = current
+            .checked_add(delta)
+            .ok_or_else(|| Error::Other(format!("increment of key {} overflows", key)))?;
+        store.insert(key, value.to_string().into_bytes(), expires)?;
+        Ok(value)
+    }
+
+    /// Replace the payload for the given key if it is equal to the old one.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        let mut store = self.store();
+        let current = store
+            .get(key, SystemTime::now())
+            .map(|e| e.value.as_slice());
+        if current != old {
+            return Ok(false);
+        }
+
+        store.insert(key, value.to_vec(), None)?;
+        Ok(true)
+    }
+
+    /// List a page of the keys starting with the given prefix, in lexicographic order.
+    /// The cursor of a page is the last key in that page.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        let limit = limit.max(1) as usize;
+        let (keys, more) = self.store().keys(prefix, cursor, limit, SystemTime::now());
+
+        let cursor = if more { keys.last().cloned() } else { None };
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        let now = SystemTime::now();
+        Ok(self.store().get(key, now).map(|e| lifetime(e.expires, now)))
+    }
+}
+
+impl MemoryCache {
+    /// Create a new, unbounded instance for the cache.
+    pub fn new() -> Self {
+        Self::builder()
+            .build()
+            .expect("creating a cache without a snapshot cannot fail")
+    }
+
+    /// Return a builder for an in-memory cache.
+    pub fn builder() -> MemoryCacheBuilder {
+        MemoryCacheBuilder::default()
+    }
+
+    /// Save the live entries of the cache to its snapshot file, if one is set.
+    pub fn save_snapshot(&self) -> anyhow::Result<()> {
+        let store = self.store();
+        match &store.snapshot {
+            Some(path) => snapshot::save(path, &store),
+            None => Ok(()),
+        }
+    }
+
+    /// Lock the store of the cache.
+    fn store(&self) -> MutexGuard<'_, Store> {
+        // The store is never left in an inconsistent state by a panic,
+        // so it can still be used if the lock is poisoned.
+        self.store.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl Default for MemoryCache {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Return the expiration time for the given time-to-live (in seconds), if any.
+fn expires(ttl: Option<u32>) -> Option<SystemTime> {
+    ttl.map(|s| SystemTime::now() + Duration::from_secs(s as u64))
+}
+
+/// Return the lifetime of a live entry with the given expiration time, with the
+/// remaining seconds rounded up.
+fn lifetime(expires: Option<SystemTime>, now: SystemTime) -> Lifetime {
+    let remaining = match expires.and_then(|t| t.duration_since(now).ok()) {
+        Some(d) => d,
+        None => return Lifetime::Persistent,
+    };
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    Lifetime::Remaining(secs.min(u32::MAX as u64) as u32)
+}
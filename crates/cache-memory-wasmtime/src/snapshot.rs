@@ -0,0 +1,117 @@
+//! Snapshots of the in-memory cache, saved to and loaded from a file.
+//!
+//! A snapshot starts with a magic header, followed by the live entries from the
+//! least to the most recently used, so loading it preserves the eviction order.
+//! Each entry is stored as its expiration time, as seconds since the Unix epoch
+//! (or zero if the entry never expires), followed by the length of the key, the
+//! key, the length of the payload, and the payload. All integers are little-endian.
+
+use crate::store::Store;
+use std::{
+    fs::File,
+    io::{BufReader, BufWriter, ErrorKind, Read, Write},
+    path::Path,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// The header identifying the format of a snapshot.
+const MAGIC: &[u8; 8] = b"WCACHE01";
+
+/// An entry loaded from a snapshot.
+pub(crate) struct SnapshotEntry {
+    pub key: String,
+    pub value: Vec<u8>,
+    pub expires: Option<SystemTime>,
+}
+
+/// Save the live entries of the store to the given file.
+/// The snapshot is first written to a temporary file, which then replaces the file.
+pub(crate) fn save(path: &Path, store: &Store) -> anyhow::Result<()> {
+    let tmp = path.with_extension("tmp");
+    let mut w = BufWriter::new(File::create(&tmp)?);
+    w.write_all(MAGIC)?;
+    for (key, entry) in store.entries(SystemTime::now()) {
+        let expires = match entry.expires {
+            Some(t) => t.duration_since(UNIX_EPOCH)?.as_secs().max(1),
+            None => 0,
+        };
+        w.write_all(&expires.to_le_bytes())?;
+        w.write_all(&(key.len() as u32).to_le_bytes())?;
+        w.write_all(key.as_bytes())?;
+        w.write_all(&(entry.value.len() as u64).to_le_bytes())?;
+        w.write_all(&entry.value)?;
+    }
+
+    w.into_inner()?.sync_all()?;
+    std::fs::rename(&tmp, path)?;
+    Ok(())
+}
+
+/// Load the entries saved in the given file, skipping the entries that have expired.
+/// A missing file is an empty snapshot.
+pub(crate) fn load(path: &Path) -> anyhow::Result<Vec<SnapshotEntry>> {
+    let mut r = match File::open(path) {
+        Ok(f) => BufReader::new(f),
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        Err(e) => return Err(e.into()),
+    };
+
+    let mut magic = [0; 8];
+    r.read_exact(&mut magic)?;
+    if &magic != MAGIC {
+        anyhow::bail!("invalid cache snapshot header in {:?}", path);
+    }
+
+    let now = SystemTime::now();
+    let mut entries = Vec::new();
+    loop {
+        let mut expires = [0; 8];
+        match r.read_exact(&mut expires) {
+            Err(e) if e.kind() == ErrorKind::UnexpectedEof => break,
+            res => res?,
+        }
+        let key_len = read_u32(&mut r)?;
+        let key = String::from_utf8(read_bytes(&mut r, key_len as u64)?)?;
+        let value_len = read_u64(&mut r)?;
+        let value = read_bytes(&mut r, value_len)?;
+
+        let expires = match u64::from_le_bytes(expires) {
+            0 => None,
+            s => Some(UNIX_EPOCH + Duration::from_secs(s)),
+        };
+        if matches!(expires, Some(t) if t <= now) {
+            continue;
+        }
+        entries.push(SnapshotEntry {
+            key,
+            value,
+            expires,
+        });
+    }
+
+    Ok(entries)
+}
+
+/// Read a little-endian `u32`.
+fn read_u32(r: &mut impl Read) -> anyhow::Result<u32> {
+    let mut buf = [0; 4];
+    r.read_exact(&mut buf)?;
+    Ok(u32::from_le_bytes(buf))
+}
+
+/// Read a little-endian `u64`.
+fn read_u64(r: &mut impl Read) -> anyhow::Result<u64> {
+    let mut buf = [0; 8];
+    r.read_exact(&mut buf)?;
+    Ok(u64::from_le_bytes(buf))
+}
+
+/// Read the given number of bytes.
+fn read_bytes(r: &mut impl Read, len: u64) -> anyhow::Result<Vec<u8>> {
+    let mut buf = Vec::new();
+    r.take(len).read_to_end(&mut buf)?;
+    if buf.len() as u64 != len {
+        anyhow::bail!("truncated cache snapshot");
+    }
+    Ok(buf)
+}
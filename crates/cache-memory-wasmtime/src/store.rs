@@ -0,0 +1,196 @@
+//! The in-memory store of cache entries, with TTL expiry and LRU eviction.
+
+use crate::wasi_cache::Error;
+use std::{collections::BTreeMap, ops::Bound, path::PathBuf, time::SystemTime};
+
+/// The limits of the store. Unset limits are unbounded.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct Limits {
+    /// The maximum number of entries.
+    pub max_entries: Option<usize>,
+    /// The maximum total size of the keys and payloads of the entries.
+    pub max_bytes: Option<usize>,
+}
+
+/// A cache entry.
+pub(crate) struct Entry {
+    /// The payload of the entry.
+    pub value: Vec<u8>,
+    /// The expiration time of the entry, if any.
+    pub expires: Option<SystemTime>,
+    /// The tick of the last access to the entry, used for LRU eviction.
+    tick: u64,
+}
+
+impl Entry {
+    /// Check whether the entry has expired.
+    pub fn is_expired(&self, now: SystemTime) -> bool {
+        matches!(self.expires, Some(t) if t <= now)
+    }
+}
+
+/// The entries of the cache, ordered by key so they can be listed in pages.
+pub(crate) struct Store {
+    entries: BTreeMap<String, Entry>,
+    /// The keys of the entries, ordered by their last access.
+    recency: BTreeMap<u64, String>,
+    /// The logical clock incremented on every access.
+    tick: u64,
+    /// The total size of the keys and payloads of the entries.
+    bytes: usize,
+    limits: Limits,
+    /// The file the entries are saved to when the store is dropped, if any.
+    pub snapshot: Option<PathBuf>,
+}
+
+impl Store {
+    /// Create an empty store with the given limits.
+    pub fn new(limits: Limits) -> Self {
+        Self {
+            entries: BTreeMap::new(),
+            recency: BTreeMap::new(),
+            tick: 0,
+            bytes: 0,
+            limits,
+            snapshot: None,
+        }
+    }
+
+    /// Return the live entry for the given key, marking it as recently used.
+    /// An expired entry is removed.
+    pub fn get(&mut self, key: &str, now: SystemTime) -> Option<&Entry> {
+        if self.entries.get(key)?.is_expired(now) {
+            self.remove(key);
+            return None;
+        }
+
+        self.tick += 1;
+        let tick = self.tick;
+        let entry = self.entries.get_mut(key)?;
+        self.recency.remove(&entry.tick);
+        self.recency.insert(tick, key.to_string());
+        entry.tick = tick;
+        Some(entry)
+    }
+
+    /// Insert an entry, replacing the existing entry for the same key, then
+    /// evict the least recently used entries that exceed the limits.
+    pub fn insert(
+        &mut self,
+        key: &str,
+        value: Vec<u8>,
+        expires: Option<SystemTime>,
+    ) -> Result<(), Error> {
+        let size = key.len() + value.len();
+        if let Some(max) = self.limits.max_bytes {
+            if size > max {
+                return Err(Error::ValueTooLarge(format!(
+                    "entry of {} bytes exceeds the maximum cache size of {} bytes",
+                    size, max
+                )));
+            }
+        }
+
+        self.remove(key);
+        self.tick += 1;
+        self.recency.insert(self.tick, key.to_string());
+        self.entries.insert(
+            key.to_string(),
+            Entry {
+                value,
+                expires,
+                tick: self.tick,
+            },
+        );
+        self.bytes += size;
+        self.evict();
+        Ok(())
+    }
+
+    /// Remove the entry for the given key, if it exists.
+    pub fn remove(&mut self, key: &str) -> Option<Entry> {
+        let entry = self.entries.remove(key)?;
+        self.recency.remove(&entry.tick);
+        self.bytes -= key.len() + entry.value.len();
+        Some(entry)
+    }
+
+    /// Return up to `limit` live keys starting with the given prefix that
+    /// come after the given key, and whether there are more keys to list.
+    pub fn keys(
+        &self,
+        prefix: &str,
+        after: Option<&str>,
+        limit: usize,
+        now: SystemTime,
+    ) -> (Vec<String>, bool) {
+        let start = match after {
+            Some(a) if a >= prefix => Bound::Excluded(a),
+            _ => Bound::Included(prefix),
+        };
+
+        let mut keys = self
+            .entries
+            .range::<str, _>((start, Bound::Unbounded))
+            .take_while(|(k, _)| k.starts_with(prefix))
+            .filter(|(_, e)| !e.is_expired(now))
+            .map(|(k, _)| k.clone())
+            .take(limit + 1)
+            .collect::<Vec<_>>();
+
+        let more = keys.len() > limit;
+        keys.truncate(limit);
+        (keys, more)
+    }
+
+    /// Return the live entries of the store, from the least to the most recently used.
+    pub fn entries(&self, now: SystemTime) -> impl Iterator<Item = (&String, &Entry)> {
+        self.recency
+            .values()
+            .filter_map(move |k| self.entries.get_key_value(k))
+            .filter(move |(_, e)| !e.is_expired(now))
+    }
+
+    /// Evict the least recently used entries until the store is within its limits.
+    /// Expired entries are removed first.
+    fn evict(&mut self) {
+        if !self.exceeds_limits() {
+            return;
+        }
+
+        let now = SystemTime::now();
+        let expired = self
+            .entries
+            .iter()
+            .filter(|(_, e)| e.is_expired(now))
+            .map(|(k, _)| k.clone())
+            .collect::<Vec<_>>();
+        for key in expired {
+            self.remove(&key);
+        }
+
+        while self.exceeds_limits() {
+            let key = match self.recency.values().next() {
+                Some(k) => k.clone(),
+                None => return,
+            };
+            self.remove(&key);
+        }
+    }
+
+    /// Check whether the store exceeds its limits.
+    fn exceeds_limits(&self) -> bool {
+        matches!(self.limits.max_entries, Some(max) if self.entries.len() > max)
+            || matches!(self.limits.max_bytes, Some(max) if self.bytes > max)
+    }
+}
+
+impl Drop for Store {
+    fn drop(&mut self) {
+        if let Some(path) = &self.snapshot {
+            if let Err(e) = crate::snapshot::save(path, self) {
+                log::error!("cannot save cache snapshot to {:?}: {:#}", path, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,282 @@
+//! Asynchronous implementation of the WASI cache interface using a Redis instance,
+//! for engines with async support enabled.
+
+use crate::{commands::Commands, error, lifetime, PoolConfig};
+use cache_wasi_wasmtime::{
+    asynchronous::wasi_cache::{self, *},
+    validate_namespace,
+};
+use redis::{aio::ConnectionManager, Client, Cmd, FromRedisValue, RedisError, RedisResult};
+use std::{future::Future, io, time::Duration};
+
+/// Asynchronous Redis implementation for the WASI cache interface.
+///
+/// Commands are sent on a single multiplexed connection shared by all clones
+/// of the cache, which is re-established when it is dropped, so the cache
+/// never blocks the thread and can be used from inside a Tokio runtime.
+#[derive(Clone)]
+pub struct AsyncRedisCache {
+    /// The address of the Redis instance.
+    pub address: String,
+
+    /// The commands of the cache, prefixing every key with the namespace, if any.
+    commands: Commands,
+
+    /// The multiplexed connection, shared by all clones of the cache.
+    conn: ConnectionManager,
+
+    /// The maximum duration of a command, if any.
+    command_timeout: Option<Duration>,
+}
+
+#[cache_wasi_wasmtime::async_trait]
+impl wasi_cache::WasiCache for AsyncRedisCache {
+    /// Set the payload for the given key.
+    /// If provided, the time-to-live argument (in seconds) will be used to set the expiration time.
+    async fn set(
+        &mut self,
+        key: &str,
+        value: PayloadParam<'_>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting key {}", key);
+        self.set(key, value, ttl).await.map_err(|e| error(e).into())
+    }
+
+    /// Get the payload for the given key, if it exists.
+    async fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
+        log::info!("getting key {}", key);
+        self.get(key).await.map_err(|e| error(e).into())
+    }
+
+    /// Delete the entry for the given key.
+    async fn delete(&mut self, key: &str) -> Result<(), Error> {
+        log::info!("deleting key {}", key);
+        self.delete(key).await.map_err(|e| error(e).into())
+    }
+
+    /// Get the payloads for the given keys.
+    async fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        self.get_many(&keys).await.map_err(|e| error(e).into())
+    }
+
+    /// Set the payloads for the given keys.
+    async fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        self.set_many(&entries, ttl)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Delete the entries for the given keys.
+    async fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        self.delete_many(&keys).await.map_err(|e| error(e).into())
+    }
+
+    /// Increment the integer stored for the given key.
+    async fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        self.increment(key, delta)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Replace the payload for the given key if it is equal to the old one.
+    async fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        self.compare_and_swap(key, old, value)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// List a page of the keys starting with the given prefix.
+    async fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        self.list_keys(prefix, cursor, limit)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    async fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        self.get_ttl(key).await.map_err(|e| error(e).into())
+    }
+}
+
+impl AsyncRedisCache {
+    /// Connect to the Redis instance at the given address. The time-outs of the
+    /// configuration are used for connecting and for each command, and the size
+    /// of the pool is ignored, since the connection is multiplexed.
+    pub(crate) async fn connect(
+        address: String,
+        namespace: Option<String>,
+        config: &PoolConfig,
+    ) -> anyhow::Result<Self> {
+        if let Some(ns) = &namespace {
+            validate_namespace(ns)?;
+        }
+
+        let client = Client::open(address.as_str())?;
+        let conn = with_timeout(
+            Some(config.connect_timeout),
+            client.get_tokio_connection_manager(),
+        )
+        .await?;
+        Ok(Self {
+            address,
+            commands: Commands::new(namespace),
+            conn,
+            command_timeout: config.command_timeout,
+        })
+    }
+
+    /// Return a clone of the cache using the given namespace, which shares
+    /// the connection of this cache.
+    pub fn with_namespace(&self, namespace: &str) -> anyhow::Result<Self> {
+        validate_namespace(namespace)?;
+        Ok(Self {
+            commands: Commands::new(Some(namespace.to_string())),
+            ..self.clone()
+        })
+    }
+
+    /// Return the namespace of the cache, if any.
+    pub fn namespace(&self) -> Option<&str> {
+        self.commands.namespace()
+    }
+
+    /// Set the payload in Redis using the given key and optional time-to-live (in seconds).
+    async fn set(&mut self, key: &str, value: &[u8], ttl: Option<u32>) -> anyhow::Result<()> {
+        self.query(self.commands.set(key, value, ttl)).await
+    }
+
+    /// Get the payload stored in Redis using the given key, if it exists.
+    async fn get(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.query(self.commands.get(key)).await
+    }
+
+    /// Delete the entry for the given key stored in Redis.
+    async fn delete(&mut self, key: &str) -> anyhow::Result<()> {
+        self.query(self.commands.delete(key)).await
+    }
+
+    /// Get the payloads stored in Redis for the given keys.
+    async fn get_many(&mut self, keys: &[&str]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        self.query(self.commands.get_many(keys)).await
+    }
+
+    /// Set the payloads in Redis in a single transaction.
+    async fn set_many(
+        &mut self,
+        entries: &[(&str, &[u8])],
+        ttl: Option<u32>,
+    ) -> anyhow::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let pipe = self.commands.set_many(entries, ttl);
+        let mut conn = self.conn.clone();
+        self.run(pipe.query_async(&mut conn)).await
+    }
+
+    /// Delete the entries for the given keys stored in Redis.
+    async fn delete_many(&mut self, keys: &[&str]) -> anyhow::Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+
+        self.query(self.commands.delete_many(keys)).await
+    }
+
+    /// Atomically increment the integer stored in Redis for the given key.
+    async fn increment(&mut self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        self.query(self.commands.increment(key, delta)).await
+    }
+
+    /// Atomically replace the payload stored in Redis for the given key.
+    async fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<&[u8]>,
+        value: &[u8],
+    ) -> anyhow::Result<bool> {
+        let invocation = self.commands.compare_and_swap(key, old, value);
+        let mut conn = self.conn.clone();
+        self.run(invocation.invoke_async(&mut conn)).await
+    }
+
+    /// List a page of the keys stored in Redis starting with the given prefix.
+    /// As with `SCAN`, the limit is only a hint and a key may be returned in more than one page.
+    async fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> anyhow::Result<KeyPage> {
+        let reply = self
+            .query(self.commands.list_keys(prefix, cursor, limit))
+            .await?;
+        let (keys, cursor) = self.commands.key_page(reply);
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry stored in Redis for the given key.
+    async fn get_ttl(&mut self, key: &str) -> anyhow::Result<Option<Lifetime>> {
+        let ttl = self.query(self.commands.get_ttl(key)).await?;
+        Ok(lifetime(ttl).map(Into::into))
+    }
+
+    /// Send a command on the multiplexed connection, failing if it does not
+    /// complete within the command time-out.
+    async fn query<T: FromRedisValue>(&self, cmd: Cmd) -> anyhow::Result<T> {
+        let mut conn = self.conn.clone();
+        self.run(cmd.query_async(&mut conn)).await
+    }
+
+    /// Run a command, failing if it does not complete within the command time-out.
+    async fn run<T>(&self, cmd: impl Future<Output = RedisResult<T>>) -> anyhow::Result<T> {
+        Ok(with_timeout(self.command_timeout, cmd).await?)
+    }
+}
+
+/// Await a Redis future, failing with a time-out error if it does not complete
+/// within the given duration, if any.
+async fn with_timeout<T>(
+    timeout: Option<Duration>,
+    fut: impl Future<Output = RedisResult<T>>,
+) -> RedisResult<T> {
+    let timeout = match timeout {
+        Some(t) => t,
+        None => return fut.await,
+    };
+
+    match tokio::time::timeout(timeout, fut).await {
+        Ok(res) => res,
+        Err(_) => Err(RedisError::from(io::Error::new(
+            io::ErrorKind::TimedOut,
+            "the Redis command timed out",
+        ))),
+    }
+}
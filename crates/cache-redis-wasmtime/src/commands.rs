@@ -0,0 +1,178 @@
+//! The Redis commands of the cache, shared by the blocking and the asynchronous
+//! implementations, which only differ in how the commands are sent.
+
+use once_cell::sync::Lazy;
+use redis::{Cmd, Pipeline, Script, ScriptInvocation, ToRedisArgs};
+
+/// Lua script replacing the value of a key if it is equal to the old value.
+/// The first argument is `1` if the key is expected to exist, and `0` otherwise.
+/// The script is only hashed once, and is then invoked with `EVALSHA`.
+static COMPARE_AND_SWAP_SCRIPT: Lazy<Script> = Lazy::new(|| {
+    Script::new(
+        r#"
+local current = redis.call('GET', KEYS[1])
+if ARGV[1] == '1' then
+    if current ~= ARGV[2] then
+        return 0
+    end
+elseif current then
+    return 0
+end
+redis.call('SET', KEYS[1], ARGV[3])
+return 1
+"#,
+    )
+});
+
+/// Builder for the Redis commands of a cache, which prefixes the keys with
+/// the namespace of the cache, if any.
+#[derive(Clone)]
+pub(crate) struct Commands {
+    namespace: Option<String>,
+}
+
+impl Commands {
+    /// Create a builder for the commands of a cache using the given namespace.
+    pub(crate) fn new(namespace: Option<String>) -> Self {
+        Self { namespace }
+    }
+
+    /// Return the namespace of the cache, if any.
+    pub(crate) fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Set the payload for the given key, with an optional time-to-live (in seconds).
+    /// Redis rejects a zero expiration time, so an entry with a zero time-to-live,
+    /// which expires immediately, is deleted instead.
+    pub(crate) fn set(&self, key: &str, value: &[u8], ttl: Option<u32>) -> Cmd {
+        if ttl == Some(0) {
+            return self.delete(key);
+        }
+
+        let mut cmd = redis::cmd("SET");
+        cmd.arg(self.key(key)).arg(value);
+        if let Some(s) = ttl {
+            cmd.arg("EX").arg(s);
+        }
+        cmd
+    }
+
+    /// Get the payload for the given key.
+    pub(crate) fn get(&self, key: &str) -> Cmd {
+        command("GET", self.key(key))
+    }
+
+    /// Delete the entry for the given key.
+    pub(crate) fn delete(&self, key: &str) -> Cmd {
+        command("DEL", self.key(key))
+    }
+
+    /// Get the payloads for the given keys using a single `MGET` command.
+    pub(crate) fn get_many(&self, keys: &[&str]) -> Cmd {
+        command("MGET", self.keys(keys))
+    }
+
+    /// Set the payloads for the given keys using a single transactional pipeline,
+    /// with an optional time-to-live (in seconds) for every entry.
+    pub(crate) fn set_many(&self, entries: &[(&str, &[u8])], ttl: Option<u32>) -> Pipeline {
+        let mut pipe = redis::pipe();
+        pipe.atomic();
+        for (key, value) in entries {
+            pipe.add_command(self.set(key, value, ttl)).ignore();
+        }
+        pipe
+    }
+
+    /// Delete the entries for the given keys using a single `DEL` command.
+    pub(crate) fn delete_many(&self, keys: &[&str]) -> Cmd {
+        command("DEL", self.keys(keys))
+    }
+
+    /// Atomically increment the integer stored for the given key using `INCRBY`.
+    pub(crate) fn increment(&self, key: &str, delta: i64) -> Cmd {
+        command("INCRBY", (self.key(key), delta))
+    }
+
+    /// Atomically replace the payload for the given key if it is equal to the old one.
+    pub(crate) fn compare_and_swap(
+        &self,
+        key: &str,
+        old: Option<&[u8]>,
+        value: &[u8],
+    ) -> ScriptInvocation<'static> {
+        let mut invocation = COMPARE_AND_SWAP_SCRIPT.key(self.key(key));
+        invocation
+            .arg(old.is_some())
+            .arg(old.unwrap_or_default())
+            .arg(value);
+        invocation
+    }
+
+    /// List a page of the keys starting with the given prefix using `SCAN`.
+    /// As with `SCAN`, the limit is only a hint and a key may be returned in more than one page.
+    pub(crate) fn list_keys(&self, prefix: &str, cursor: Option<&str>, limit: u32) -> Cmd {
+        let pattern = format!("{}*", escape_pattern(&self.key(prefix)));
+        let args = (
+            cursor.unwrap_or("0"),
+            "MATCH",
+            pattern,
+            "COUNT",
+            limit.max(1),
+        );
+        command("SCAN", args)
+    }
+
+    /// Convert the reply of `SCAN` into the keys, without the namespace, and the
+    /// cursor of the next page, if any.
+    pub(crate) fn key_page(&self, reply: (String, Vec<String>)) -> (Vec<String>, Option<String>) {
+        let (next, keys) = reply;
+        let keys = match &self.namespace {
+            Some(ns) => keys
+                .iter()
+                .filter_map(|k| k.strip_prefix(ns.as_str())?.strip_prefix(':'))
+                .map(String::from)
+                .collect(),
+            None => keys,
+        };
+        let cursor = if next == "0" { None } else { Some(next) };
+        (keys, cursor)
+    }
+
+    /// Get the remaining lifetime (in milliseconds) of the entry for the given key using `PTTL`.
+    pub(crate) fn get_ttl(&self, key: &str) -> Cmd {
+        command("PTTL", self.key(key))
+    }
+
+    /// Return the key stored in Redis for the given key, prefixed with the namespace.
+    fn key(&self, key: &str) -> String {
+        match &self.namespace {
+            Some(ns) => format!("{}:{}", ns, key),
+            None => key.to_string(),
+        }
+    }
+
+    /// Return the keys stored in Redis for the given keys.
+    fn keys(&self, keys: &[&str]) -> Vec<String> {
+        keys.iter().map(|k| self.key(k)).collect()
+    }
+}
+
+/// Return the command with the given name and arguments.
+fn command(name: &str, args: impl ToRedisArgs) -> Cmd {
+    let mut cmd = redis::cmd(name);
+    cmd.arg(args);
+    cmd
+}
+
+/// Escape the characters of the given string that have a special meaning in Redis glob patterns.
+fn escape_pattern(s: &str) -> String {
+    let mut pattern = String::with_capacity(s.len());
+    for c in s.chars() {
+        if matches!(c, '*' | '?' | '[' | ']' | '\\') {
+            pattern.push('\\');
+        }
+        pattern.push(c);
+    }
+    pattern
+}
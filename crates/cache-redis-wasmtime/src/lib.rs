@@ -1,13 +1,20 @@
-//! Impement the WASI cache interface using a Redis instance.
+//! Implement the WASI cache interface using a Redis instance.
 //! This is using a Wasmtime host implementation.
 
-use redis::{Client, Commands};
-use std::sync::Arc;
-use wasi_cache::*;
+mod asynchronous;
+mod commands;
+mod pool;
 
-pub use wasi_cache::add_to_linker;
+use cache_wasi_wasmtime::validate_namespace;
+use commands::Commands;
+use pool::Pool;
+use redis::{Client, ErrorKind, RedisError};
+use wasi_cache::*;
 
-wit_bindgen_wasmtime::export!("wit/ephemeral/wasi-cache.wit");
+pub use asynchronous::AsyncRedisCache;
+pub use cache_wasi_wasmtime::asynchronous::wasi_cache::add_to_linker as add_to_linker_async;
+pub use cache_wasi_wasmtime::wasi_cache::{self, add_to_linker};
+pub use pool::{PoolConfig, PoolMetrics};
 
 /// Redis implementation for the WASI cache interface.
 #[derive(Clone)]
@@ -15,8 +22,54 @@ pub struct RedisCache {
     /// The address of the Redis instance.
     pub address: String,
 
-    /// The Redis client.
-    client: Arc<Client>,
+    /// The commands of the cache, prefixing every key with the namespace, if any.
+    commands: Commands,
+
+    /// The pool of connections, shared by all clones of the cache.
+    pool: Pool,
+}
+
+/// Builder for a Redis cache.
+pub struct RedisCacheBuilder {
+    address: String,
+    namespace: Option<String>,
+    pool: PoolConfig,
+}
+
+impl RedisCacheBuilder {
+    /// Set the namespace of the cache, which isolates its keys from the keys
+    /// of caches using other namespaces. Caches without a namespace can access
+    /// the keys of every namespace.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Set the configuration of the connection pool.
+    pub fn pool_config(mut self, config: PoolConfig) -> Self {
+        self.pool = config;
+        self
+    }
+
+    /// Create the cache. Connections are opened when needed, so this does not connect to Redis.
+    pub fn build(self) -> anyhow::Result<RedisCache> {
+        if let Some(ns) = &self.namespace {
+            validate_namespace(ns)?;
+        }
+
+        let client = Client::open(self.address.as_str())?;
+        Ok(RedisCache {
+            address: self.address,
+            commands: Commands::new(self.namespace),
+            pool: Pool::new(client, &self.pool),
+        })
+    }
+
+    /// Connect to Redis and create an asynchronous cache, for engines with async
+    /// support enabled. The cache uses a single multiplexed connection instead of a pool.
+    pub async fn build_async(self) -> anyhow::Result<AsyncRedisCache> {
+        AsyncRedisCache::connect(self.address, self.namespace, &self.pool).await
+    }
 }
 
 impl wasi_cache::WasiCache for RedisCache {
@@ -24,64 +77,253 @@ impl wasi_cache::WasiCache for RedisCache {
     /// If provided, the time-to-live argument (in seconds) will be used to set the expiration time.
     fn set(&mut self, key: &str, value: PayloadParam<'_>, ttl: Option<u32>) -> Result<(), Error> {
         log::info!("setting key {}", key);
-        self.set(key, value, ttl)?;
-        Ok(())
+        self.set(key, value, ttl).map_err(error)
     }
 
-    /// Get the payload for the given key.
-    fn get(&mut self, key: &str) -> Result<PayloadResult, Error> {
+    /// Get the payload for the given key, if it exists.
+    fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
         log::info!("getting key {}", key);
-        Ok(self.get(key)?)
+        self.get(key).map_err(error)
     }
 
     /// Delete the entry for the given key.
     fn delete(&mut self, key: &str) -> Result<(), Error> {
         log::info!("deleting key {}", key);
-        Ok(self.delete(key)?)
+        self.delete(key).map_err(error)
+    }
+
+    /// Get the payloads for the given keys.
+    fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        self.get_many(&keys).map_err(error)
+    }
+
+    /// Set the payloads for the given keys.
+    fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        self.set_many(&entries, ttl).map_err(error)
+    }
+
+    /// Delete the entries for the given keys.
+    fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        self.delete_many(&keys).map_err(error)
+    }
+
+    /// Increment the integer stored for the given key.
+    fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        self.increment(key, delta).map_err(error)
+    }
+
+    /// Replace the payload for the given key if it is equal to the old one.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        self.compare_and_swap(key, old, value).map_err(error)
+    }
+
+    /// List a page of the keys starting with the given prefix.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        self.list_keys(prefix, cursor, limit).map_err(error)
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        self.get_ttl(key).map_err(error)
     }
 }
 
 impl RedisCache {
-    /// Create a new instance for the cache.
+    /// Create a new instance for the cache, using the default pool configuration.
     pub fn new(addr: &str) -> anyhow::Result<Self> {
-        let client = Arc::new(Client::open(addr)?);
-        Ok(RedisCache {
+        Self::builder(addr).build()
+    }
+
+    /// Create a new instance for the cache, using the given pool configuration.
+    /// Connections are opened when needed, so this does not connect to Redis.
+    pub fn with_config(addr: &str, config: &PoolConfig) -> anyhow::Result<Self> {
+        Self::builder(addr).pool_config(config.clone()).build()
+    }
+
+    /// Return a builder for a cache using the Redis instance at the given address.
+    pub fn builder(addr: &str) -> RedisCacheBuilder {
+        RedisCacheBuilder {
             address: addr.to_string(),
-            client,
+            namespace: None,
+            pool: PoolConfig::default(),
+        }
+    }
+
+    /// Return a clone of the cache using the given namespace, which shares
+    /// the connection pool of this cache.
+    pub fn with_namespace(&self, namespace: &str) -> anyhow::Result<Self> {
+        validate_namespace(namespace)?;
+        Ok(Self {
+            commands: Commands::new(Some(namespace.to_string())),
+            ..self.clone()
         })
     }
 
-    /// Set the payload in Redis using the given key and optional time-to-live (in seconds).
-    fn set(&mut self, key: &str, value: &[u8], ttl: Option<u32>) -> anyhow::Result<()> {
-        let mut conn = self.client.get_connection()?;
-        conn.set(key, value)?;
-        match ttl {
-            Some(s) => conn.expire(key, s as usize)?,
-            None => {}
-        };
+    /// Return the namespace of the cache, if any.
+    pub fn namespace(&self) -> Option<&str> {
+        self.commands.namespace()
+    }
 
-        Ok(())
+    /// Return the usage metrics of the connection pool.
+    pub fn metrics(&self) -> PoolMetrics {
+        self.pool.metrics()
     }
 
-    /// Get the payload stored in Redis using the given key.
-    fn get(&mut self, key: &str) -> anyhow::Result<Vec<u8>> {
-        let mut conn = self.client.get_connection()?;
-        let res: Vec<u8> = conn.get(key)?;
+    /// Set the payload in Redis using the given key and optional time-to-live (in seconds).
+    fn set(&mut self, key: &str, value: &[u8], ttl: Option<u32>) -> anyhow::Result<()> {
+        self.pool
+            .run(|conn| self.commands.set(key, value, ttl).query(conn))
+    }
 
-        Ok(res)
+    /// Get the payload stored in Redis using the given key, if it exists.
+    fn get(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        self.pool.run(|conn| self.commands.get(key).query(conn))
     }
 
     /// Delete the entry for the given key stored in Redis.
     fn delete(&mut self, key: &str) -> anyhow::Result<()> {
-        let mut conn = self.client.get_connection()?;
-        conn.del(key)?;
+        self.pool.run(|conn| self.commands.delete(key).query(conn))
+    }
+
+    /// Get the payloads stored in Redis for the given keys.
+    fn get_many(&mut self, keys: &[&str]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        if keys.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        let cmd = self.commands.get_many(keys);
+        self.pool.run(|conn| cmd.query(conn))
+    }
+
+    /// Set the payloads in Redis in a single transaction. The transaction is not
+    /// retried if the connection is dropped, since it may have been applied.
+    fn set_many(&mut self, entries: &[(&str, &[u8])], ttl: Option<u32>) -> anyhow::Result<()> {
+        if entries.is_empty() {
+            return Ok(());
+        }
+
+        let pipe = self.commands.set_many(entries, ttl);
+        self.pool.run_once(|conn| pipe.query(conn))
+    }
+
+    /// Delete the entries for the given keys stored in Redis.
+    fn delete_many(&mut self, keys: &[&str]) -> anyhow::Result<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
 
-        Ok(())
+        let cmd = self.commands.delete_many(keys);
+        self.pool.run(|conn| cmd.query(conn))
+    }
+
+    /// Atomically increment the integer stored in Redis for the given key.
+    /// The command is not retried if the connection is dropped, since it may have been applied.
+    fn increment(&mut self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        self.pool
+            .run_once(|conn| self.commands.increment(key, delta).query(conn))
+    }
+
+    /// Atomically replace the payload stored in Redis for the given key.
+    /// The script is not retried if the connection is dropped, since it may have been applied.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<&[u8]>,
+        value: &[u8],
+    ) -> anyhow::Result<bool> {
+        let invocation = self.commands.compare_and_swap(key, old, value);
+        self.pool.run_once(|conn| invocation.invoke(conn))
+    }
+
+    /// List a page of the keys stored in Redis starting with the given prefix.
+    /// The cursor is the one returned by Redis, and as with `SCAN`, the limit is only a hint
+    /// and a key may be returned in more than one page. The namespace is removed from the keys.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> anyhow::Result<KeyPage> {
+        let cmd = self.commands.list_keys(prefix, cursor, limit);
+        let reply = self.pool.run(|conn| cmd.query(conn))?;
+        let (keys, cursor) = self.commands.key_page(reply);
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry stored in Redis for the given key.
+    fn get_ttl(&mut self, key: &str) -> anyhow::Result<Option<Lifetime>> {
+        let ttl = self
+            .pool
+            .run(|conn| self.commands.get_ttl(key).query(conn))?;
+        Ok(lifetime(ttl))
     }
 }
 
-impl From<anyhow::Error> for Error {
-    fn from(_: anyhow::Error) -> Self {
-        Self::Error
+/// Convert the reply of `PTTL` into the lifetime of an entry, with the remaining
+/// seconds rounded up. Redis replies with `-2` for a missing key, and with `-1`
+/// for a key that does not expire.
+fn lifetime(pttl: i64) -> Option<Lifetime> {
+    match pttl {
+        -2 => None,
+        -1 => Some(Lifetime::Persistent),
+        ms => {
+            let secs = (ms.max(0) as u64).div_ceil(1000);
+            Some(Lifetime::Remaining(secs.min(u32::MAX as u64) as u32))
+        }
+    }
+}
+
+/// Convert an error from the cache into an error for the WASI cache interface.
+fn error(e: anyhow::Error) -> Error {
+    let e = match e.downcast::<RedisError>() {
+        Ok(e) => return redis_error(e),
+        Err(e) => e,
+    };
+    // The pool fails to provide a connection when Redis cannot be reached.
+    match e.downcast::<r2d2::Error>() {
+        Ok(e) => Error::BackendUnavailable(e.to_string()),
+        Err(e) => Error::Other(format!("{:#}", e)),
+    }
+}
+
+/// Convert a Redis error into an error for the WASI cache interface.
+fn redis_error(e: RedisError) -> Error {
+    let msg = e.to_string();
+    if e.is_timeout() {
+        return Error::Timeout(msg);
+    }
+    if e.is_io_error() || e.is_connection_refusal() || e.is_connection_dropped() {
+        return Error::BackendUnavailable(msg);
+    }
+
+    match e.kind() {
+        ErrorKind::AuthenticationFailed => Error::PermissionDenied(msg),
+        ErrorKind::BusyLoadingError
+        | ErrorKind::TryAgain
+        | ErrorKind::ClusterDown
+        | ErrorKind::MasterDown
+        | ErrorKind::ReadOnly => Error::BackendUnavailable(msg),
+        _ => Error::Other(msg),
     }
 }
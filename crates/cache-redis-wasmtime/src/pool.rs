@@ -0,0 +1,203 @@
+//! Pooling of the connections to a Redis instance.
+
+use redis::{Client, Connection, ConnectionLike, RedisError, RedisResult};
+use std::{
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+/// Configuration for the connection pool of a Redis cache.
+#[derive(Clone, Debug)]
+pub struct PoolConfig {
+    /// The maximum number of connections kept open by the pool.
+    pub max_size: u32,
+    /// The timeout for opening a new connection, which is also the
+    /// maximum time spent waiting for a connection from the pool.
+    pub connect_timeout: Duration,
+    /// The timeout for reading and writing a single command, if any.
+    pub command_timeout: Option<Duration>,
+}
+
+impl Default for PoolConfig {
+    fn default() -> Self {
+        Self {
+            max_size: 16,
+            connect_timeout: Duration::from_secs(5),
+            command_timeout: Some(Duration::from_secs(5)),
+        }
+    }
+}
+
+/// Usage metrics of the connection pool of a Redis cache.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct PoolMetrics {
+    /// The number of open connections, idle or in use.
+    pub connections: u32,
+    /// The number of idle connections.
+    pub idle_connections: u32,
+    /// The number of connections opened since the pool was created.
+    pub connects: u64,
+    /// The number of failed attempts to open a connection.
+    pub connect_failures: u64,
+    /// The number of idempotent commands retried on a new connection
+    /// after their connection was dropped.
+    pub reconnects: u64,
+}
+
+/// A pool of connections to a Redis instance, shared by all its clones.
+#[derive(Clone)]
+pub(crate) struct Pool {
+    inner: r2d2::Pool<ConnectionManager>,
+    counters: Arc<Counters>,
+}
+
+impl Pool {
+    /// Create a new pool. Connections are only opened when needed,
+    /// so this does not fail if the instance is unavailable.
+    pub(crate) fn new(client: Client, config: &PoolConfig) -> Self {
+        let counters = Arc::new(Counters::default());
+        let manager = ConnectionManager {
+            client,
+            connect_timeout: config.connect_timeout,
+            command_timeout: config.command_timeout,
+            counters: counters.clone(),
+        };
+        let inner = r2d2::Pool::builder()
+            .max_size(config.max_size)
+            .min_idle(Some(0))
+            .connection_timeout(config.connect_timeout)
+            // Broken connections are detected when a command fails instead.
+            .test_on_check_out(false)
+            .build_unchecked(manager);
+
+        Self { inner, counters }
+    }
+
+    /// Run the given command, which must be safe to repeat, with a connection
+    /// from the pool.
+    ///
+    /// A connection that failed with an I/O error is discarded rather than
+    /// returned to the pool. If the connection was dropped, which happens
+    /// when the server closes connections while they are idle in the pool,
+    /// the command is retried once with a new connection. Since the server
+    /// may have run the command before the connection was dropped, this is
+    /// only used for idempotent commands, such as `GET`, `SET`, and `DEL`.
+    pub(crate) fn run<T>(
+        &self,
+        f: impl Fn(&mut Connection) -> RedisResult<T>,
+    ) -> anyhow::Result<T> {
+        self.run_with_retry(true, f)
+    }
+
+    /// Run the given command with a connection from the pool, without retrying
+    /// it if the connection was dropped, for commands that are not safe to repeat,
+    /// such as `INCRBY` or transactions.
+    pub(crate) fn run_once<T>(
+        &self,
+        f: impl Fn(&mut Connection) -> RedisResult<T>,
+    ) -> anyhow::Result<T> {
+        self.run_with_retry(false, f)
+    }
+
+    fn run_with_retry<T>(
+        &self,
+        mut retry: bool,
+        f: impl Fn(&mut Connection) -> RedisResult<T>,
+    ) -> anyhow::Result<T> {
+        loop {
+            let mut conn = self.inner.get()?;
+            match f(&mut conn.inner) {
+                Err(e) if e.is_io_error() => {
+                    conn.broken = true;
+                    if !retry || !e.is_connection_dropped() {
+                        return Err(e.into());
+                    }
+                    retry = false;
+                    self.counters.reconnects.fetch_add(1, Ordering::Relaxed);
+                }
+                res => return Ok(res?),
+            }
+        }
+    }
+
+    /// Return the usage metrics of the pool.
+    pub(crate) fn metrics(&self) -> PoolMetrics {
+        let state = self.inner.state();
+        PoolMetrics {
+            connections: state.connections,
+            idle_connections: state.idle_connections,
+            connects: self.counters.connects.load(Ordering::Relaxed),
+            connect_failures: self.counters.connect_failures.load(Ordering::Relaxed),
+            reconnects: self.counters.reconnects.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// Counters updated by the pool, shared by all its clones.
+#[derive(Default)]
+struct Counters {
+    connects: AtomicU64,
+    connect_failures: AtomicU64,
+    reconnects: AtomicU64,
+}
+
+/// A pooled connection, which is discarded by the pool once broken.
+struct PooledConnection {
+    inner: Connection,
+    broken: bool,
+}
+
+/// Open and check the connections of the pool.
+struct ConnectionManager {
+    client: Client,
+    connect_timeout: Duration,
+    command_timeout: Option<Duration>,
+    counters: Arc<Counters>,
+}
+
+impl ConnectionManager {
+    /// Open a new connection, setting its command timeouts.
+    fn open(&self) -> RedisResult<Connection> {
+        let conn = self
+            .client
+            .get_connection_with_timeout(self.connect_timeout)?;
+        conn.set_read_timeout(self.command_timeout)?;
+        conn.set_write_timeout(self.command_timeout)?;
+        Ok(conn)
+    }
+}
+
+impl r2d2::ManageConnection for ConnectionManager {
+    type Connection = PooledConnection;
+    type Error = RedisError;
+
+    fn connect(&self) -> Result<Self::Connection, Self::Error> {
+        match self.open() {
+            Ok(inner) => {
+                self.counters.connects.fetch_add(1, Ordering::Relaxed);
+                Ok(PooledConnection {
+                    inner,
+                    broken: false,
+                })
+            }
+            Err(e) => {
+                self.counters
+                    .connect_failures
+                    .fetch_add(1, Ordering::Relaxed);
+                log::warn!("cannot connect to Redis: {}", e);
+                Err(e)
+            }
+        }
+    }
+
+    fn is_valid(&self, conn: &mut Self::Connection) -> Result<(), Self::Error> {
+        redis::cmd("PING").query(&mut conn.inner)
+    }
+
+    fn has_broken(&self, conn: &mut Self::Connection) -> bool {
+        conn.broken || !conn.inner.is_open()
+    }
+}
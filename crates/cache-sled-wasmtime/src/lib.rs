@@ -0,0 +1,398 @@
+//! Implement the WASI cache interface using an embedded sled database.
+//! This is using a Wasmtime host implementation.
+
+mod sweeper;
+
+use sled::{
+    transaction::{abort, ConflictableTransactionResult, TransactionError, TransactionalTree},
+    Transactional, Tree,
+};
+use std::{
+    ops::Bound,
+    path::Path,
+    sync::{mpsc, Arc},
+    time::Duration,
+};
+use wasi_cache::*;
+
+use cache_wasi_wasmtime::parse_integer;
+pub use cache_wasi_wasmtime::wasi_cache::{self, add_to_linker};
+
+/// The name of the tree mapping the keys to their entries.
+const ENTRIES_TREE: &str = "entries";
+
+/// The name of the tree indexing the keys by their expiration time.
+const EXPIRY_TREE: &str = "expiry";
+
+/// The default interval between two sweeps of the expired entries.
+const DEFAULT_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Sled implementation for the WASI cache interface.
+///
+/// Each entry is stored in the `entries` tree as its expiration time, as
+/// seconds since the Unix epoch (or zero if it never expires), followed by
+/// the payload. Entries that expire are also stored in the `expiry` tree,
+/// keyed by their expiration time and key, which the sweeper uses to remove
+/// them in order. Expired entries that were not removed yet, and entries too
+/// short to hold an expiration time, are treated as missing.
+#[derive(Clone)]
+pub struct SledCache {
+    /// The entries of the cache.
+    entries: Tree,
+
+    /// The index of the keys by expiration time.
+    expiry: Tree,
+
+    /// Stops the sweeper once the last clone of the cache is dropped.
+    _sweeper: Arc<mpsc::SyncSender<()>>,
+}
+
+/// Builder for a sled cache.
+pub struct SledCacheBuilder {
+    config: sled::Config,
+    sweep_interval: Duration,
+}
+
+impl SledCacheBuilder {
+    /// Set the interval between two sweeps of the expired entries.
+    pub fn sweep_interval(mut self, interval: Duration) -> Self {
+        self.sweep_interval = interval;
+        self
+    }
+
+    /// Set the maximum size (in bytes) of the page cache of the database.
+    pub fn cache_capacity(mut self, bytes: u64) -> Self {
+        self.config = self.config.cache_capacity(bytes);
+        self
+    }
+
+    /// Open the database, and start the sweeper on a background thread.
+    pub fn build(self) -> anyhow::Result<SledCache> {
+        let db = self.config.open()?;
+        let entries = db.open_tree(ENTRIES_TREE)?;
+        let expiry = db.open_tree(EXPIRY_TREE)?;
+
+        let stop = sweeper::spawn(entries.clone(), expiry.clone(), self.sweep_interval)?;
+        Ok(SledCache {
+            entries,
+            expiry,
+            _sweeper: Arc::new(stop),
+        })
+    }
+}
+
+impl wasi_cache::WasiCache for SledCache {
+    /// Set the payload for the given key.
+    /// If provided, the time-to-live argument (in seconds) will be used to set the expiration time.
+    fn set(&mut self, key: &str, value: PayloadParam<'_>, ttl: Option<u32>) -> Result<(), Error> {
+        log::info!("setting key {}", key);
+        let expires = expires(ttl)?;
+        (&self.entries, &self.expiry)
+            .transaction(|(entries, expiry)| {
+                put(entries, expiry, key, value, expires)?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        Ok(())
+    }
+
+    /// Get the payload for the given key, if it exists.
+    fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
+        log::info!("getting key {}", key);
+        let now = now()?;
+        let value = self.entries.get(key).map_err(storage_error)?;
+        Ok(value.and_then(|v| live_payload(&v, now)))
+    }
+
+    /// Delete the entry for the given key.
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        log::info!("deleting key {}", key);
+        (&self.entries, &self.expiry)
+            .transaction(|(entries, expiry)| {
+                remove(entries, expiry, key)?;
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        Ok(())
+    }
+
+    /// Get the payloads for the given keys.
+    fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        keys.into_iter().map(|k| self.get(k)).collect()
+    }
+
+    /// Set the payloads for the given keys in a single transaction.
+    fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        let expires = expires(ttl)?;
+        (&self.entries, &self.expiry)
+            .transaction(|(tx_entries, tx_expiry)| {
+                for (key, value) in &entries {
+                    put(tx_entries, tx_expiry, key, value, expires)?;
+                }
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        Ok(())
+    }
+
+    /// Delete the entries for the given keys in a single transaction.
+    fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        (&self.entries, &self.expiry)
+            .transaction(|(entries, expiry)| {
+                for key in &keys {
+                    remove(entries, expiry, key)?;
+                }
+                Ok(())
+            })
+            .map_err(transaction_error)?;
+        Ok(())
+    }
+
+    /// Increment the integer stored for the given key in a transaction.
+    /// The expiration time of an existing entry is preserved.
+    fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        let now = now()?;
+        let value = (&self.entries, &self.expiry)
+            .transaction(|(entries, expiry)| {
+                let current = entries.get(key)?;
+                let (current, expires) = match current.as_deref().and_then(|v| live(v, now)) {
+                    Some((expires, payload)) => match parse_integer(payload) {
+                        Ok(i) => (i, expires),
+                        Err(e) => return abort(e),
+                    },
+                    None => (0, 0),
+                };
+
+                let value = match current.checked_add(delta) {
+                    Some(v) => v,
+                    None => {
+                        return abort(Error::Other(format!("increment of key {} overflows", key)))
+                    }
+                };
+                put(entries, expiry, key, value.to_string().as_bytes(), expires)?;
+                Ok(value)
+            })
+            .map_err(transaction_error)?;
+        Ok(value)
+    }
+
+    /// Replace the payload for the given key in a transaction, if it is equal to the old one.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        let now = now()?;
+        let swapped = (&self.entries, &self.expiry)
+            .transaction(|(entries, expiry)| {
+                let current = entries.get(key)?.and_then(|v| live_payload(&v, now));
+                if current.as_deref() != old {
+                    return Ok(false);
+                }
+
+                put(entries, expiry, key, value, 0)?;
+                Ok(true)
+            })
+            .map_err(transaction_error)?;
+        Ok(swapped)
+    }
+
+    /// List a page of the keys starting with the given prefix, in lexicographic order.
+    /// The cursor of a page is the last key in that page.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        let now = now()?;
+        let limit = limit.max(1) as usize;
+
+        let start: Bound<&[u8]> = match cursor {
+            Some(c) if c >= prefix => Bound::Excluded(c.as_bytes()),
+            _ => Bound::Included(prefix.as_bytes()),
+        };
+
+        let mut keys = Vec::new();
+        for item in self.entries.range::<&[u8], _>((start, Bound::Unbounded)) {
+            let (key, value) = item.map_err(storage_error)?;
+            if !key.starts_with(prefix.as_bytes()) {
+                break;
+            }
+            if live(&value, now).is_none() {
+                continue;
+            }
+            keys.push(
+                String::from_utf8(key.to_vec())
+                    .map_err(|e| Error::Other(format!("invalid key: {}", e)))?,
+            );
+            if keys.len() > limit {
+                break;
+            }
+        }
+
+        let more = keys.len() > limit;
+        keys.truncate(limit);
+        let cursor = if more { keys.last().cloned() } else { None };
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        let now = now()?;
+        let value = self.entries.get(key).map_err(storage_error)?;
+        Ok(value
+            .as_deref()
+            .and_then(|v| live(v, now))
+            .map(|(expires, _)| match expires {
+                0 => Lifetime::Persistent,
+                expires => Lifetime::Remaining((expires - now).min(u32::MAX as u64) as u32),
+            }))
+    }
+}
+
+impl SledCache {
+    /// Open the cache stored in the given directory, using the default configuration.
+    pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Self::builder(path).build()
+    }
+
+    /// Return a builder for a cache stored in the given directory.
+    pub fn builder(path: impl AsRef<Path>) -> SledCacheBuilder {
+        SledCacheBuilder {
+            config: sled::Config::new().path(path),
+            sweep_interval: DEFAULT_SWEEP_INTERVAL,
+        }
+    }
+
+    /// Remove the expired entries now, rather than waiting for the sweeper.
+    /// Return the number of removed entries.
+    pub fn sweep(&self) -> anyhow::Result<usize> {
+        Ok(sweeper::sweep(
+            &self.entries,
+            &self.expiry,
+            sweeper::now()?,
+        )?)
+    }
+
+    /// Flush the pending writes to disk.
+    pub fn flush(&self) -> anyhow::Result<()> {
+        self.entries.flush()?;
+        self.expiry.flush()?;
+        Ok(())
+    }
+}
+
+/// Write an entry in a transaction, updating the expiry index.
+fn put(
+    entries: &TransactionalTree,
+    expiry: &TransactionalTree,
+    key: &str,
+    payload: &[u8],
+    expires: u64,
+) -> ConflictableTransactionResult<(), Error> {
+    remove(entries, expiry, key)?;
+
+    let mut value = Vec::with_capacity(8 + payload.len());
+    value.extend_from_slice(&expires.to_be_bytes());
+    value.extend_from_slice(payload);
+    entries.insert(key.as_bytes(), value)?;
+    if expires != 0 {
+        expiry.insert(index_key(expires, key.as_bytes()), Vec::new())?;
+    }
+    Ok(())
+}
+
+/// Remove an entry in a transaction, updating the expiry index.
+fn remove(
+    entries: &TransactionalTree,
+    expiry: &TransactionalTree,
+    key: &str,
+) -> ConflictableTransactionResult<(), Error> {
+    if let Some(old) = entries.remove(key.as_bytes())? {
+        // Entries too short to hold an expiration time were never indexed.
+        match decode(&old) {
+            Some((expires, _)) if expires != 0 => {
+                expiry.remove(index_key(expires, key.as_bytes()))?;
+            }
+            _ => {}
+        }
+    }
+    Ok(())
+}
+
+/// Return the key of an entry in the expiry index. The expiration time is
+/// big-endian so that the index is ordered by expiration time.
+fn index_key(expires: u64, key: &[u8]) -> Vec<u8> {
+    let mut index_key = Vec::with_capacity(8 + key.len());
+    index_key.extend_from_slice(&expires.to_be_bytes());
+    index_key.extend_from_slice(key);
+    index_key
+}
+
+/// Split a stored entry into its expiration time and its payload, or return
+/// `None` if the entry is too short to hold an expiration time.
+fn decode(value: &[u8]) -> Option<(u64, &[u8])> {
+    if value.len() < 8 {
+        return None;
+    }
+    let (expires, payload) = value.split_at(8);
+    Some((u64::from_be_bytes(expires.try_into().ok()?), payload))
+}
+
+/// Split a stored entry into its expiration time and its payload, if it has
+/// not expired. Entries too short to hold an expiration time are treated as expired.
+fn live(value: &[u8], now: u64) -> Option<(u64, &[u8])> {
+    decode(value).filter(|(expires, _)| *expires == 0 || *expires > now)
+}
+
+/// Return the payload of a stored entry, if it has not expired.
+fn live_payload(value: &[u8], now: u64) -> Option<Vec<u8>> {
+    live(value, now).map(|(_, payload)| payload.to_vec())
+}
+
+/// Return the expiration time for the given time-to-live (in seconds), or zero.
+fn expires(ttl: Option<u32>) -> Result<u64, Error> {
+    match ttl {
+        Some(s) => Ok(now()? + s as u64),
+        None => Ok(0),
+    }
+}
+
+/// Return the current time as seconds since the Unix epoch.
+fn now() -> Result<u64, Error> {
+    sweeper::now().map_err(|e| Error::Other(e.to_string()))
+}
+
+/// Convert the error of a transaction into an error for the WASI cache interface.
+fn transaction_error(e: TransactionError<Error>) -> Error {
+    match e {
+        TransactionError::Abort(e) => e,
+        TransactionError::Storage(e) => storage_error(e),
+    }
+}
+
+/// Convert a sled error into an error for the WASI cache interface.
+fn storage_error(e: sled::Error) -> Error {
+    let msg = e.to_string();
+    match e {
+        sled::Error::Io(e) => match e.kind() {
+            std::io::ErrorKind::PermissionDenied => Error::PermissionDenied(msg),
+            std::io::ErrorKind::TimedOut => Error::Timeout(msg),
+            _ => Error::Other(msg),
+        },
+        _ => Error::Other(msg),
+    }
+}
@@ -0,0 +1,76 @@
+//! Background removal of the expired entries of the cache.
+
+use sled::{transaction::TransactionError, Transactional, Tree};
+use std::{
+    sync::mpsc::{self, RecvTimeoutError},
+    thread,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+/// Start a thread removing the expired entries at the given interval.
+/// The thread stops once the returned sender, or all its clones, are dropped.
+pub(crate) fn spawn(
+    entries: Tree,
+    expiry: Tree,
+    interval: Duration,
+) -> std::io::Result<mpsc::SyncSender<()>> {
+    let (stop, stopped) = mpsc::sync_channel(1);
+    thread::Builder::new()
+        .name("cache-sweeper".to_string())
+        .spawn(move || loop {
+            match stopped.recv_timeout(interval) {
+                Err(RecvTimeoutError::Timeout) => {}
+                _ => return,
+            }
+
+            let res = now().and_then(|now| Ok(sweep(&entries, &expiry, now)?));
+            match res {
+                Ok(0) => {}
+                Ok(n) => log::info!("removed {} expired entries", n),
+                Err(e) => log::error!("cannot remove expired entries: {:#}", e),
+            }
+        })?;
+
+    Ok(stop)
+}
+
+/// Remove the entries that expired at the given time, as seconds since the Unix epoch.
+/// Return the number of removed entries.
+pub(crate) fn sweep(entries: &Tree, expiry: &Tree, now: u64) -> sled::Result<usize> {
+    let mut removed = 0;
+    // The index is ordered by expiration time, so the expired entries come first.
+    for item in expiry.range(..(now + 1).to_be_bytes()) {
+        let (index_key, _) = item?;
+        // Every index key starts with an expiration time, but the index is
+        // stored on disk, so a corrupted key is skipped rather than trusted.
+        if index_key.len() < 8 {
+            continue;
+        }
+        let (expires, key) = index_key.split_at(8);
+
+        let res = (entries, expiry).transaction(|(tx_entries, tx_expiry)| {
+            // The entry may have been replaced since the index was read.
+            let current = tx_entries.get(key)?;
+            let expired = matches!(&current, Some(v) if v.starts_with(expires));
+            if expired {
+                tx_entries.remove(key)?;
+            }
+            tx_expiry.remove(index_key.as_ref())?;
+            Ok(expired)
+        });
+
+        match res {
+            Ok(true) => removed += 1,
+            Ok(false) => {}
+            Err(TransactionError::Storage(e)) => return Err(e),
+            Err(TransactionError::Abort(())) => unreachable!("the sweeper never aborts"),
+        }
+    }
+
+    Ok(removed)
+}
+
+/// Return the current time as seconds since the Unix epoch.
+pub(crate) fn now() -> anyhow::Result<u64> {
+    Ok(SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs())
+}
@@ -0,0 +1,67 @@
+//! Wasmtime host bindings for the WASI cache interface, shared by the cache
+//! implementations so that they can be composed.
+
+mod tiered;
+
+pub use tiered::*;
+pub use wit_bindgen_wasmtime::async_trait;
+
+wit_bindgen_wasmtime::export!("wit/ephemeral/wasi-cache.wit");
+
+/// Asynchronous host bindings, for engines with async support enabled, whose
+/// host implementations await the backing service instead of blocking the thread.
+pub mod asynchronous {
+    wit_bindgen_wasmtime::export!({
+        paths: ["wit/ephemeral/wasi-cache.wit"],
+        async: *,
+    });
+}
+
+/// Check that the namespace of a cache is not empty, and does not contain the `:`
+/// separator, so that the keys of different namespaces never collide.
+///
+/// Caches with a namespace store their keys as `namespace:key`, and caches without
+/// one store them unchanged. Namespaces therefore only isolate the caches that use
+/// one: a cache without a namespace can read, write, and list the keys of every
+/// namespace, so every untrusted guest module must be given its own namespace.
+pub fn validate_namespace(namespace: &str) -> anyhow::Result<()> {
+    if namespace.is_empty() || namespace.contains(':') {
+        anyhow::bail!(
+            "invalid namespace {:?}: must be non-empty and not contain ':'",
+            namespace
+        );
+    }
+    Ok(())
+}
+
+/// Parse a payload containing a decimal integer, as stored by `increment`.
+pub fn parse_integer(payload: &[u8]) -> Result<i64, wasi_cache::Error> {
+    std::str::from_utf8(payload)
+        .ok()
+        .and_then(|s| s.parse().ok())
+        .ok_or_else(|| wasi_cache::Error::Other("value is not an integer".to_string()))
+}
+
+impl From<wasi_cache::Error> for asynchronous::wasi_cache::Error {
+    fn from(e: wasi_cache::Error) -> Self {
+        use wasi_cache::Error::*;
+        match e {
+            NotFound(msg) => Self::NotFound(msg),
+            InvalidKey(msg) => Self::InvalidKey(msg),
+            ValueTooLarge(msg) => Self::ValueTooLarge(msg),
+            BackendUnavailable(msg) => Self::BackendUnavailable(msg),
+            Timeout(msg) => Self::Timeout(msg),
+            PermissionDenied(msg) => Self::PermissionDenied(msg),
+            Other(msg) => Self::Other(msg),
+        }
+    }
+}
+
+impl From<wasi_cache::Lifetime> for asynchronous::wasi_cache::Lifetime {
+    fn from(l: wasi_cache::Lifetime) -> Self {
+        match l {
+            wasi_cache::Lifetime::Persistent => Self::Persistent,
+            wasi_cache::Lifetime::Remaining(secs) => Self::Remaining(secs),
+        }
+    }
+}
@@ -0,0 +1,422 @@
+//! Composition of two cache implementations, with a local L1 in front of a remote L2.
+
+use crate::wasi_cache::{Error, KeyPage, Lifetime, PayloadParam, PayloadResult, WasiCache};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, Instant},
+};
+
+/// How the writes are propagated to the tiers of a tiered cache.
+/// With every policy, reads that miss the L1 are read through from the L2,
+/// and the entries that were read are stored in the L1.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Policy {
+    /// Writes go to the L2 and remove the entries from the L1,
+    /// which is only populated by reads.
+    ReadThrough,
+    /// Writes go to the L2, then to the L1.
+    WriteThrough,
+    /// Writes go to the L1, and are buffered until they are flushed to the L2,
+    /// either explicitly, once the number of buffered writes reaches a limit,
+    /// by the first operation after the oldest one reached a maximum age, or
+    /// when the last clone of the cache is dropped. Until then, other hosts
+    /// read the previous entries from the L2, and the buffered writes are lost
+    /// if the host stops without dropping the cache or if the flush fails.
+    WriteBack,
+}
+
+/// Configuration for a tiered cache.
+#[derive(Clone, Debug)]
+pub struct TieredConfig {
+    /// The policy for propagating the writes.
+    pub policy: Policy,
+    /// The maximum time-to-live (in seconds) of the entries in the L1, if any.
+    /// Entries without a time-to-live are stored in the L1 with this one, which
+    /// bounds how long the L1 can serve an entry modified by another host.
+    pub l1_max_ttl: Option<u32>,
+    /// The number of buffered writes that triggers a flush with the write-back policy.
+    pub max_pending: usize,
+    /// The age of the oldest buffered write that triggers a flush with the
+    /// write-back policy, on the next operation, if any.
+    pub max_pending_age: Option<Duration>,
+}
+
+impl Default for TieredConfig {
+    fn default() -> Self {
+        Self {
+            policy: Policy::ReadThrough,
+            l1_max_ttl: Some(60),
+            max_pending: 1024,
+            max_pending_age: Some(Duration::from_secs(1)),
+        }
+    }
+}
+
+/// The writes buffered with the write-back policy.
+#[derive(Default)]
+struct Buffer {
+    /// The buffered write of every key.
+    writes: BTreeMap<String, Pending>,
+    /// The time the oldest buffered write was buffered, if any.
+    since: Option<Instant>,
+}
+
+/// A write buffered with the write-back policy.
+enum Pending {
+    /// Set the payload, with an optional expiration time.
+    Set(Vec<u8>, Option<Instant>),
+    /// Delete the entry.
+    Delete,
+}
+
+/// Cache implementation composing two other implementations, typically a
+/// local L1 (such as an in-memory cache) in front of a remote L2.
+///
+/// The L2 is the source of truth: the atomic operations and the listing of
+/// keys are performed on the L2, after flushing the buffered writes.
+#[derive(Clone)]
+pub struct TieredCache<L1: WasiCache, L2: WasiCache> {
+    l1: L1,
+    l2: L2,
+    config: TieredConfig,
+    /// The writes buffered with the write-back policy, shared by all clones of the cache.
+    pending: Arc<Mutex<Buffer>>,
+}
+
+impl<L1: WasiCache, L2: WasiCache> TieredCache<L1, L2> {
+    /// Create a new instance for the cache, composing the given tiers.
+    pub fn new(l1: L1, l2: L2, config: TieredConfig) -> Self {
+        Self {
+            l1,
+            l2,
+            config,
+            pending: Arc::default(),
+        }
+    }
+
+    /// Write the buffered writes to the L2. The writes that failed, and the ones
+    /// that were not attempted after a failure, remain buffered.
+    pub fn flush(&mut self) -> Result<(), Error> {
+        let (writes, since) = {
+            let mut pending = self.pending();
+            (std::mem::take(&mut pending.writes), pending.since.take())
+        };
+        let mut ops = writes.into_iter();
+        while let Some((key, op)) = ops.next() {
+            if let Err(e) = self.write(&key, &op) {
+                // Writes buffered since the flush started are more recent, and take precedence.
+                let mut pending = self.pending();
+                for (key, op) in std::iter::once((key, op)).chain(ops) {
+                    pending.writes.entry(key).or_insert(op);
+                }
+                pending.since = match (since, pending.since) {
+                    (Some(a), Some(b)) => Some(a.min(b)),
+                    (a, b) => a.or(b),
+                };
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Flush the buffered writes if the oldest one reached the maximum age.
+    /// A failure is only logged, since the writes remain buffered, and the
+    /// operation that triggered the flush does not depend on it.
+    fn flush_stale(&mut self) {
+        let stale = match (self.pending().since, self.config.max_pending_age) {
+            (Some(since), Some(max)) => since.elapsed() >= max,
+            _ => false,
+        };
+        if stale {
+            if let Err(e) = self.flush() {
+                log::warn!("cannot flush the buffered writes to the L2 cache: {:?}", e);
+            }
+        }
+    }
+
+    /// Write the buffered write for the given key to the L2, if any.
+    fn flush_key(&mut self, key: &str) -> Result<(), Error> {
+        let op = match self.pending().writes.remove(key) {
+            Some(op) => op,
+            None => return Ok(()),
+        };
+
+        let res = self.write(key, &op);
+        if res.is_err() {
+            self.pending().writes.entry(key.to_string()).or_insert(op);
+        }
+        res
+    }
+
+    /// Write a buffered write to the L2. A write whose entry expired while
+    /// it was buffered deletes the entry, since it replaced the previous one.
+    fn write(&mut self, key: &str, op: &Pending) -> Result<(), Error> {
+        match op {
+            Pending::Set(value, None) => self.l2.set(key, value, None),
+            Pending::Set(value, Some(expires)) => match remaining_ttl(*expires, Instant::now()) {
+                Some(ttl) => self.l2.set(key, value, Some(ttl)),
+                None => self.l2.delete(key),
+            },
+            Pending::Delete => self.l2.delete(key),
+        }
+    }
+
+    /// Buffer a write, then flush the buffered writes if they reached the limit.
+    fn buffer(&mut self, ops: Vec<(String, Pending)>) -> Result<(), Error> {
+        let len = {
+            let mut pending = self.pending();
+            pending.writes.extend(ops);
+            pending.since.get_or_insert_with(Instant::now);
+            pending.writes.len()
+        };
+
+        if len >= self.config.max_pending {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    /// Store entries in the L1, with their time-to-live capped for the L1.
+    /// Since the L2 has the entries, a failure only removes them from the L1.
+    fn fill(&mut self, entries: Vec<(&str, PayloadParam<'_>)>, ttl: Option<u32>) {
+        let ttl = self.l1_ttl(ttl);
+        let keys = entries.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+        if let Err(e) = self.l1.set_many(entries, ttl) {
+            log::warn!("cannot store {} keys in the L1 cache: {:?}", keys.len(), e);
+            let _ = self.l1.delete_many(keys);
+        }
+    }
+
+    /// Return the time-to-live (in seconds) of an entry stored in the L1.
+    fn l1_ttl(&self, ttl: Option<u32>) -> Option<u32> {
+        match (ttl, self.config.l1_max_ttl) {
+            (Some(t), Some(max)) => Some(t.min(max)),
+            (t, max) => t.or(max),
+        }
+    }
+
+    /// Return the buffered write for the given key, if any.
+    /// The outer option is `None` if there is no buffered write for the key.
+    fn pending_value(&self, key: &str) -> Option<Option<Vec<u8>>> {
+        match self.pending().writes.get(key)? {
+            Pending::Set(_, Some(expires)) if *expires <= Instant::now() => Some(None),
+            Pending::Set(value, _) => Some(Some(value.clone())),
+            Pending::Delete => Some(None),
+        }
+    }
+
+    /// Lock the buffered writes.
+    fn pending(&self) -> MutexGuard<'_, Buffer> {
+        self.pending.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl<L1: WasiCache, L2: WasiCache> WasiCache for TieredCache<L1, L2> {
+    /// Set the payload for the given key, according to the write policy.
+    fn set(&mut self, key: &str, value: PayloadParam<'_>, ttl: Option<u32>) -> Result<(), Error> {
+        self.set_many(vec![(key, value)], ttl)
+    }
+
+    /// Get the payload for the given key from the first tier that has it.
+    fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
+        let mut values = self.get_many(vec![key])?;
+        Ok(values.pop().flatten())
+    }
+
+    /// Delete the entry for the given key, according to the write policy.
+    fn delete(&mut self, key: &str) -> Result<(), Error> {
+        self.delete_many(vec![key])
+    }
+
+    /// Get the payloads for the given keys. The keys missing from the L1 are
+    /// read from the L2 in a single operation, and stored in the L1.
+    fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        self.flush_stale();
+        let mut values = vec![None; keys.len()];
+        let mut misses = Vec::new();
+        for (i, key) in keys.iter().enumerate() {
+            match self.pending_value(key) {
+                Some(v) => values[i] = v,
+                None => misses.push(i),
+            }
+        }
+
+        // The L1 is only an optimization, so the L2 is used if the L1 fails.
+        if !misses.is_empty() {
+            let l1_keys = misses.iter().map(|&i| keys[i]).collect();
+            match self.l1.get_many(l1_keys) {
+                Ok(l1_values) => {
+                    let mut remaining = Vec::new();
+                    for (i, v) in misses.into_iter().zip(l1_values) {
+                        match v {
+                            Some(v) => values[i] = Some(v),
+                            None => remaining.push(i),
+                        }
+                    }
+                    misses = remaining;
+                }
+                Err(e) => log::warn!("cannot read from the L1 cache: {:?}", e),
+            }
+        }
+
+        if !misses.is_empty() {
+            let l2_keys = misses.iter().map(|&i| keys[i]).collect();
+            let l2_values = self.l2.get_many(l2_keys)?;
+            for (&i, v) in misses.iter().zip(l2_values) {
+                values[i] = v;
+            }
+
+            // The entries are stored in the L1 with the lifetime they have left
+            // in the L2, so that the L1 does not serve them once they expired.
+            for &i in &misses {
+                let (key, value) = match values[i].as_deref() {
+                    Some(v) => (keys[i], v),
+                    None => continue,
+                };
+                match self.l2.get_ttl(key) {
+                    Ok(Some(Lifetime::Persistent)) => self.fill(vec![(key, value)], None),
+                    Ok(Some(Lifetime::Remaining(s))) => self.fill(vec![(key, value)], Some(s)),
+                    // The entry expired, or was deleted, since it was read.
+                    Ok(None) => {}
+                    Err(e) => log::warn!(
+                        "cannot get the lifetime of key {} from the L2 cache: {:?}",
+                        key,
+                        e
+                    ),
+                }
+            }
+        }
+
+        Ok(values)
+    }
+
+    /// Set the payloads for the given keys, according to the write policy.
+    fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        self.flush_stale();
+        let keys = entries.iter().map(|(k, _)| *k).collect::<Vec<_>>();
+        match self.config.policy {
+            Policy::ReadThrough => {
+                self.l2.set_many(entries, ttl)?;
+                self.l1.delete_many(keys)
+            }
+            Policy::WriteThrough => {
+                self.l2.set_many(entries.clone(), ttl)?;
+                self.fill(entries, ttl);
+                Ok(())
+            }
+            Policy::WriteBack => {
+                let expires = ttl.map(|s| Instant::now() + Duration::from_secs(s as u64));
+                let ops = entries
+                    .iter()
+                    .map(|(k, v)| (k.to_string(), Pending::Set(v.to_vec(), expires)))
+                    .collect();
+                self.fill(entries, ttl);
+                self.buffer(ops)
+            }
+        }
+    }
+
+    /// Delete the entries for the given keys, according to the write policy.
+    fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        self.flush_stale();
+        match self.config.policy {
+            Policy::ReadThrough | Policy::WriteThrough => {
+                self.l2.delete_many(keys.clone())?;
+                self.l1.delete_many(keys)
+            }
+            Policy::WriteBack => {
+                let ops = keys
+                    .iter()
+                    .map(|k| (k.to_string(), Pending::Delete))
+                    .collect();
+                self.l1.delete_many(keys)?;
+                self.buffer(ops)
+            }
+        }
+    }
+
+    /// Increment the integer stored in the L2 for the given key.
+    fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        self.flush_stale();
+        self.flush_key(key)?;
+        let value = self.l2.increment(key, delta)?;
+        self.l1.delete(key)?;
+        Ok(value)
+    }
+
+    /// Replace the payload stored in the L2 for the given key, if it is equal to the old one.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        self.flush_stale();
+        self.flush_key(key)?;
+        let swapped = self.l2.compare_and_swap(key, old, value)?;
+        if swapped {
+            self.l1.delete(key)?;
+        }
+        Ok(swapped)
+    }
+
+    /// List a page of the keys stored in the L2 starting with the given prefix.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        self.flush()?;
+        self.l2.list_keys(prefix, cursor, limit)
+    }
+
+    /// Get the remaining lifetime of the entry for the given key from its buffered
+    /// write, if any, or from the L2, since the lifetime of the L1 entries is capped.
+    fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        self.flush_stale();
+        match self.pending().writes.get(key) {
+            Some(Pending::Set(_, None)) => return Ok(Some(Lifetime::Persistent)),
+            Some(Pending::Set(_, Some(expires))) => {
+                return Ok(remaining_ttl(*expires, Instant::now()).map(Lifetime::Remaining))
+            }
+            Some(Pending::Delete) => return Ok(None),
+            None => {}
+        }
+        self.l2.get_ttl(key)
+    }
+}
+
+impl<L1: WasiCache, L2: WasiCache> Drop for TieredCache<L1, L2> {
+    /// Flush the buffered writes when the last clone of the cache is dropped.
+    fn drop(&mut self) {
+        if Arc::strong_count(&self.pending) > 1 || self.pending().writes.is_empty() {
+            return;
+        }
+        if let Err(e) = self.flush() {
+            let pending = self.pending().writes.len();
+            log::error!(
+                "cannot flush {} buffered writes to the L2 cache: {:?}",
+                pending,
+                e
+            );
+        }
+    }
+}
+
+/// Return the remaining time-to-live (in seconds, rounded up) of an entry
+/// with the given expiration time, or `None` if it has expired.
+fn remaining_ttl(expires: Instant, now: Instant) -> Option<u32> {
+    let remaining = expires.checked_duration_since(now)?;
+    if remaining.is_zero() {
+        return None;
+    }
+    let secs = remaining.as_secs() + u64::from(remaining.subsec_nanos() > 0);
+    Some(secs.min(u32::MAX as u64) as u32)
+}
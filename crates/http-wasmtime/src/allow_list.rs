@@ -0,0 +1,257 @@
+//! The destinations guest modules are allowed to send requests to.
+
+use ipnet::IpNet;
+use std::net::IpAddr;
+use url::{Host, Url};
+
+/// A list of the destinations guest modules are allowed to send requests to,
+/// parsed once from its entries. An empty list does not allow any request.
+///
+/// Each entry is one of:
+///
+/// - `*`, which allows every destination.
+/// - An IP address or network in CIDR notation, such as `10.0.0.0/8` or
+///   `fd00::/8`, which allows requests to any of its addresses, with any
+///   scheme and port. Only requests to IP addresses match these entries.
+/// - A URL of the form `[scheme://]host[:port][/path]`, which allows requests
+///   matching every part of it:
+///   - The scheme is `http`, `https`, or `*`. Without a scheme, or with `*`,
+///     both `http` and `https` are allowed.
+///   - The host is a domain, such as `example.com`, a wildcard for its
+///     subdomains, such as `*.example.com`, which does not match `example.com`
+///     itself, an IP address, or `*` for any host.
+///   - The port is a number, or `*` for any port. Without a port, only the
+///     default port of the scheme of the request is allowed.
+///   - The path is a prefix of the allowed paths, matching whole segments, so
+///     `/api` allows `/api` and `/api/users`, but not `/apis`. Without a path,
+///     every path is allowed.
+///
+/// Previously, only the host of the entries was compared, so an entry such as
+/// `https://example.com` also allowed `http://example.com` and every port. Such
+/// entries now only allow their scheme and its default port, and the previous
+/// behavior is kept by entries of the form `*://example.com:*`.
+#[derive(Clone, Debug, Default)]
+pub struct AllowList {
+    rules: Vec<Rule>,
+}
+
+/// A parsed entry of an allow-list.
+#[derive(Clone, Debug)]
+enum Rule {
+    /// Allow every destination.
+    Any,
+    /// Allow the IP addresses of a network.
+    Network(IpNet),
+    /// Allow the URLs matching the pattern.
+    Url(UrlPattern),
+}
+
+/// A pattern matching URLs, parsed from an entry of an allow-list.
+#[derive(Clone, Debug)]
+struct UrlPattern {
+    /// The allowed scheme, or `None` for both `http` and `https`.
+    scheme: Option<String>,
+    host: HostPattern,
+    port: PortPattern,
+    /// The prefix of the allowed paths, without a trailing `/`.
+    path: String,
+}
+
+#[derive(Clone, Debug)]
+enum HostPattern {
+    /// Any host.
+    Any,
+    /// The subdomains of a domain, by their suffix, which is the domain
+    /// prefixed with `.`.
+    Subdomains(String),
+    /// The host, serialized as in URLs.
+    Exact(String),
+}
+
+#[derive(Clone, Debug)]
+enum PortPattern {
+    /// Any port.
+    Any,
+    /// The default port of the scheme of the request.
+    Default,
+    /// The port.
+    Exact(u16),
+}
+
+impl AllowList {
+    /// Parse the entries of an allow-list, failing on the first invalid entry.
+    pub fn parse<S: AsRef<str>>(entries: &[S]) -> anyhow::Result<Self> {
+        let rules = entries
+            .iter()
+            .map(|e| {
+                let e = e.as_ref();
+                parse_rule(e.trim())
+                    .map_err(|msg| anyhow::anyhow!("invalid allowed destination {:?}: {}", e, msg))
+            })
+            .collect::<anyhow::Result<_>>()?;
+        Ok(Self { rules })
+    }
+
+    /// Return a list allowing every destination.
+    pub fn allow_all() -> Self {
+        Self {
+            rules: vec![Rule::Any],
+        }
+    }
+
+    /// Check whether a request to the URL is allowed by any entry of the list.
+    pub fn allows(&self, url: &Url) -> bool {
+        self.rules.iter().any(|r| r.matches(url))
+    }
+
+    /// Check whether an entry of the list explicitly allows the address,
+    /// either as part of a network, or as the host of a URL.
+    pub(crate) fn allows_ip(&self, ip: IpAddr) -> bool {
+        let host: Host = match ip {
+            IpAddr::V4(ip) => Host::Ipv4(ip),
+            IpAddr::V6(ip) => Host::Ipv6(ip),
+        };
+        let host = host.to_string();
+        self.rules.iter().any(|r| match r {
+            Rule::Network(net) => net.contains(&ip),
+            Rule::Url(UrlPattern {
+                host: HostPattern::Exact(h),
+                ..
+            }) => *h == host,
+            _ => false,
+        })
+    }
+}
+
+impl Rule {
+    fn matches(&self, url: &Url) -> bool {
+        match self {
+            Rule::Any => true,
+            Rule::Network(net) => match ip_addr(url) {
+                Some(ip) => net.contains(&ip),
+                None => false,
+            },
+            Rule::Url(pattern) => pattern.matches(url),
+        }
+    }
+}
+
+impl UrlPattern {
+    fn matches(&self, url: &Url) -> bool {
+        let scheme = match &self.scheme {
+            Some(s) => url.scheme() == s,
+            None => matches!(url.scheme(), "http" | "https"),
+        };
+        let host = match (&self.host, url.host_str()) {
+            (HostPattern::Any, Some(_)) => true,
+            (HostPattern::Subdomains(suffix), Some(h)) => {
+                h.len() > suffix.len() && h.ends_with(suffix.as_str())
+            }
+            (HostPattern::Exact(host), Some(h)) => h == host,
+            (_, None) => false,
+        };
+        // URLs do not have a port if it is the default one of their scheme.
+        let port = match self.port {
+            PortPattern::Any => true,
+            PortPattern::Default => url.port().is_none(),
+            PortPattern::Exact(p) => url.port_or_known_default() == Some(p),
+        };
+        let path = match url.path().strip_prefix(self.path.as_str()) {
+            Some(rest) => rest.is_empty() || rest.starts_with('/'),
+            None => false,
+        };
+
+        scheme && host && port && path
+    }
+}
+
+/// Parse an entry of an allow-list, returning the reason it is invalid otherwise.
+fn parse_rule(entry: &str) -> Result<Rule, String> {
+    if entry == "*" {
+        return Ok(Rule::Any);
+    }
+    if let Ok(net) = entry.parse::<IpNet>() {
+        return Ok(Rule::Network(net));
+    }
+    if let Ok(ip) = entry.parse::<IpAddr>() {
+        return Ok(Rule::Network(ip.into()));
+    }
+
+    let (scheme, rest) = match entry.split_once("://") {
+        Some((s, rest)) => (s.to_ascii_lowercase(), rest),
+        None => {
+            // Without a scheme, a `:` can only precede the port, as in `example.com:8080`.
+            if let Some((s, rest)) = entry.split_once(':') {
+                let port = rest.split('/').next().unwrap_or_default();
+                if !s.starts_with('[') && port != "*" && port.parse::<u16>().is_err() {
+                    return Err(format!("expected `://` or a port after {:?}", s));
+                }
+            }
+            ("*".to_string(), entry)
+        }
+    };
+    let scheme = match scheme.as_str() {
+        "*" => None,
+        "http" | "https" => Some(scheme),
+        _ => return Err(format!("unsupported scheme {:?}", scheme)),
+    };
+
+    let (authority, path) = match rest.find('/') {
+        Some(i) => rest.split_at(i),
+        None => (rest, ""),
+    };
+    if path.contains(['?', '#']) {
+        return Err("the path must not have a query or a fragment".to_string());
+    }
+    if authority.contains('@') {
+        return Err("the destination must not have credentials".to_string());
+    }
+
+    // The port follows the last `:`, unless it is part of an IPv6 address.
+    let (host, port) = match authority.rfind(':') {
+        Some(i) if !authority[i..].contains(']') => (&authority[..i], Some(&authority[i + 1..])),
+        _ => (authority, None),
+    };
+    let port = match port {
+        None => PortPattern::Default,
+        Some("*") => PortPattern::Any,
+        Some(p) => PortPattern::Exact(p.parse().map_err(|_| format!("invalid port {:?}", p))?),
+    };
+    let host = match host {
+        "*" => HostPattern::Any,
+        _ => match host.strip_prefix("*.") {
+            Some(domain) => match parse_host(domain)? {
+                Host::Domain(d) => HostPattern::Subdomains(format!(".{}", d)),
+                _ => return Err("wildcards are only supported for domains".to_string()),
+            },
+            None => HostPattern::Exact(parse_host(host)?.to_string()),
+        },
+    };
+
+    Ok(Rule::Url(UrlPattern {
+        scheme,
+        host,
+        port,
+        path: path.trim_end_matches('/').to_string(),
+    }))
+}
+
+/// Parse a host as in URLs, normalizing it.
+fn parse_host(host: &str) -> Result<Host, String> {
+    if host.contains('*') {
+        return Err(format!(
+            "invalid host {:?}: wildcards are only supported as the first label",
+            host
+        ));
+    }
+    Host::parse(host).map_err(|e| format!("invalid host {:?}: {}", host, e))
+}
+
+/// Return the IP address of the host of the URL, if it is one.
+pub(crate) fn ip_addr(url: &Url) -> Option<IpAddr> {
+    match url.host()? {
+        Host::Ipv4(ip) => Some(ip.into()),
+        Host::Ipv6(ip) => Some(ip.into()),
+        Host::Domain(_) => None,
+    }
+}
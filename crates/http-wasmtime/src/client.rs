@@ -0,0 +1,118 @@
+//! The HTTP client shared by the outbound requests of an implementation.
+
+use crate::guard::{self, Guard, GuardedResolver};
+use reqwest::{Certificate, Proxy};
+use std::{sync::Arc, time::Duration};
+
+/// Configuration for the HTTP client shared by the outbound requests.
+#[derive(Clone, Debug)]
+pub struct ClientConfig {
+    /// The timeout for connecting to a destination, if any.
+    pub connect_timeout: Option<Duration>,
+    /// The timeout for a whole request, from connecting to a destination
+    /// until the body of the response is read, if any.
+    pub timeout: Option<Duration>,
+    /// The maximum number of idle connections kept open for each host.
+    pub pool_max_idle_per_host: usize,
+    /// The time after which idle connections are closed, if any.
+    pub pool_idle_timeout: Option<Duration>,
+    /// The URL of the proxy the requests are sent through, if any. Otherwise,
+    /// the proxies of the system environment variables, such as `HTTPS_PROXY`,
+    /// are used. Since a proxy resolves the destinations itself, proxies cannot
+    /// be used when private networks are blocked: a configured proxy is refused,
+    /// and the proxies of the environment variables are ignored.
+    pub proxy: Option<String>,
+    /// PEM-encoded root certificates, trusted in addition to the ones of the system.
+    pub root_certificates: Vec<Vec<u8>>,
+    /// The user agent of the requests, if any.
+    pub user_agent: Option<String>,
+}
+
+impl Default for ClientConfig {
+    fn default() -> Self {
+        Self {
+            connect_timeout: Some(Duration::from_secs(10)),
+            timeout: None,
+            pool_max_idle_per_host: 32,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            proxy: None,
+            root_certificates: Vec::new(),
+            user_agent: None,
+        }
+    }
+}
+
+/// The configuration of the client, with its proxy and certificates parsed,
+/// and the guard checking the destinations.
+#[derive(Clone)]
+pub(crate) struct ClientSettings {
+    config: ClientConfig,
+    proxy: Option<Proxy>,
+    root_certificates: Vec<Certificate>,
+    guard: Guard,
+}
+
+impl ClientSettings {
+    /// Parse the configuration, failing if the proxy or a certificate is invalid,
+    /// or if a proxy is configured while private networks are blocked.
+    pub fn new(config: ClientConfig, guard: Guard) -> anyhow::Result<Self> {
+        let proxy = match &config.proxy {
+            Some(p) if guard.block_private_networks => {
+                anyhow::bail!(
+                    "proxy {:?} cannot be used when private networks are blocked",
+                    p
+                )
+            }
+            Some(p) => Some(
+                Proxy::all(p.as_str())
+                    .map_err(|e| anyhow::anyhow!("invalid proxy {:?}: {}", p, e))?,
+            ),
+            None => None,
+        };
+        let root_certificates = config
+            .root_certificates
+            .iter()
+            .enumerate()
+            .map(|(i, pem)| {
+                Certificate::from_pem(pem)
+                    .map_err(|e| anyhow::anyhow!("invalid root certificate {}: {}", i, e))
+            })
+            .collect::<anyhow::Result<_>>()?;
+
+        Ok(Self {
+            config,
+            proxy,
+            root_certificates,
+            guard,
+        })
+    }
+
+    /// Build a client with the settings. If private networks are blocked, the
+    /// client checks the addresses it connects to and the redirects it follows.
+    /// The client runs its connections on its own thread, with its own runtime.
+    pub fn client(&self) -> reqwest::Result<reqwest::blocking::Client> {
+        let config = &self.config;
+        let mut builder = reqwest::blocking::Client::builder()
+            .pool_max_idle_per_host(config.pool_max_idle_per_host)
+            .pool_idle_timeout(config.pool_idle_timeout)
+            .connect_timeout(config.connect_timeout)
+            .timeout(config.timeout);
+        if let Some(p) = &self.proxy {
+            builder = builder.proxy(p.clone());
+        }
+        for cert in &self.root_certificates {
+            builder = builder.add_root_certificate(cert.clone());
+        }
+        if let Some(ua) = &config.user_agent {
+            builder = builder.user_agent(ua.as_str());
+        }
+        if self.guard.block_private_networks {
+            // A proxy would resolve the destinations without the guard.
+            builder = builder
+                .no_proxy()
+                .dns_resolver(Arc::new(GuardedResolver::new(self.guard.clone())))
+                .redirect(guard::redirect_policy(self.guard.clone()));
+        }
+        builder.build()
+    }
+}
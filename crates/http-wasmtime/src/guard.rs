@@ -0,0 +1,188 @@
+//! Protection against requests from guest modules to private networks, such as
+//! the services of the host, which an allowed destination could otherwise lead
+//! to by resolving to a private address, or by redirecting to one.
+
+use crate::allow_list::{ip_addr, AllowList};
+use reqwest::{
+    dns::{Addrs, Name, Resolve, Resolving},
+    redirect::{Attempt, Policy},
+};
+use std::{
+    fmt,
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr},
+    sync::Arc,
+};
+
+/// The maximum number of redirects followed for a request.
+const MAX_REDIRECTS: usize = 10;
+
+/// The checks of the destinations of requests, shared by the implementation
+/// and its client, which checks the addresses it connects to and the redirects
+/// it follows.
+#[derive(Clone, Default)]
+pub(crate) struct Guard {
+    /// The destinations guest modules are allowed to send requests to.
+    pub allowed: Arc<AllowList>,
+    /// Whether requests to private networks are blocked, unless explicitly allowed.
+    pub block_private_networks: bool,
+}
+
+/// Error for a request to a destination guest modules are not allowed to
+/// send requests to, found while sending the request.
+#[derive(Debug)]
+pub(crate) struct NotAllowed(String);
+
+impl fmt::Display for NotAllowed {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "destination not allowed: {}", self.0)
+    }
+}
+
+impl std::error::Error for NotAllowed {}
+
+/// Check whether an error was caused by a destination that is not allowed.
+pub(crate) fn is_not_allowed(err: &(dyn std::error::Error + 'static)) -> bool {
+    let mut source = Some(err);
+    while let Some(e) = source {
+        if e.is::<NotAllowed>() {
+            return true;
+        }
+        source = e.source();
+    }
+    false
+}
+
+/// DNS resolver that removes the private addresses a name resolves to, unless
+/// the allow-list explicitly allows them, and fails if no address remains.
+/// Since the connection is made to the remaining addresses, a name cannot
+/// resolve to a public address when checked, then to a private one.
+pub(crate) struct GuardedResolver {
+    guard: Guard,
+}
+
+impl GuardedResolver {
+    pub fn new(guard: Guard) -> Self {
+        Self { guard }
+    }
+}
+
+impl Resolve for GuardedResolver {
+    fn resolve(&self, name: Name) -> Resolving {
+        Box::pin(resolve(self.guard.clone(), name))
+    }
+}
+
+async fn resolve(
+    guard: Guard,
+    name: Name,
+) -> Result<Addrs, Box<dyn std::error::Error + Send + Sync>> {
+    let host = name.as_str();
+    let addrs = tokio::net::lookup_host((host, 0))
+        .await?
+        .filter(|a| !guard.is_blocked(a.ip()))
+        .collect::<Vec<SocketAddr>>();
+    if addrs.is_empty() {
+        let msg = format!("{} only resolves to private addresses", host);
+        return Err(Box::new(NotAllowed(msg)));
+    }
+    Ok(Box::new(addrs.into_iter()))
+}
+
+/// Return the redirect policy checking that every redirect is allowed.
+pub(crate) fn redirect_policy(guard: Guard) -> Policy {
+    Policy::custom(move |attempt: Attempt| {
+        if attempt.previous().len() >= MAX_REDIRECTS {
+            return attempt.error("too many redirects");
+        }
+        if !guard.is_allowed(attempt.url()) {
+            let msg = format!("redirect to {}", attempt.url());
+            return attempt.error(NotAllowed(msg));
+        }
+        attempt.follow()
+    })
+}
+
+impl Guard {
+    /// Check if guest module is allowed to send request to URL, based on the list of
+    /// allowed destinations defined by the runtime.
+    pub(crate) fn is_allowed(&self, url: &reqwest::Url) -> bool {
+        self.allowed.allows(url) && !self.is_blocked_url(url)
+    }
+
+    /// Check whether a request to the address is blocked, because it belongs
+    /// to a private network and is not explicitly allowed by the allow-list.
+    pub(crate) fn is_blocked(&self, ip: IpAddr) -> bool {
+        self.block_private_networks && is_private(ip) && !self.allowed.allows_ip(ip)
+    }
+
+    /// Check whether a request to the URL is blocked, if its host is a private address.
+    pub(crate) fn is_blocked_url(&self, url: &reqwest::Url) -> bool {
+        match ip_addr(url) {
+            Some(ip) => self.is_blocked(ip),
+            None => false,
+        }
+    }
+}
+
+/// Check whether the address belongs to a network that is not publicly
+/// routable, such as the loopback, private, and link-local networks.
+fn is_private(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_private_v4(ip),
+        IpAddr::V6(ip) => is_private_v6(ip),
+    }
+}
+
+fn is_private_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        || ip.is_private()
+        || ip.is_link_local()
+        || ip.is_multicast()
+        // "This network" (0.0.0.0/8), and shared address space (100.64.0.0/10).
+        || a == 0
+        || (a == 100 && (b & 0xc0) == 64)
+        // IETF protocol assignments (192.0.0.0/24), and benchmarking (198.18.0.0/15).
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (b & 0xfe) == 18)
+        // Reserved (240.0.0.0/4), including the broadcast address.
+        || a >= 240
+}
+
+fn is_private_v6(ip: Ipv6Addr) -> bool {
+    if let Some(ip) = embedded_v4(ip) {
+        return is_private_v4(ip);
+    }
+
+    let segments = ip.segments();
+    ip.is_unspecified()
+        || ip.is_loopback()
+        // Unique local (fc00::/7), link-local (fe80::/10), deprecated site-local
+        // (fec0::/10), and multicast (ff00::/8) addresses.
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        || (segments[0] & 0xff00) == 0xff00
+}
+
+/// Return the IPv4 address embedded in an IPv6 address that leads to it, which
+/// is then checked as an IPv4 address: IPv4-mapped (::ffff:0:0/96), IPv4-compatible
+/// (::/96), NAT64 (64:ff9b::/96), and 6to4 (2002::/16) addresses, and Teredo
+/// (2001::/32) addresses, whose last 32 bits are the inverted address of the client.
+fn embedded_v4(ip: Ipv6Addr) -> Option<Ipv4Addr> {
+    let segments = ip.segments();
+    let [.., a, b, c, d] = ip.octets();
+    if (segments[..5] == [0; 5] && (segments[5] == 0xffff || segments[5] == 0))
+        || segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0]
+    {
+        Some(Ipv4Addr::new(a, b, c, d))
+    } else if segments[0] == 0x2002 {
+        let octets = ip.octets();
+        Some(Ipv4Addr::new(octets[2], octets[3], octets[4], octets[5]))
+    } else if segments[..2] == [0x2001, 0] {
+        Some(Ipv4Addr::new(!a, !b, !c, !d))
+    } else {
+        None
+    }
+}
@@ -1,89 +1,172 @@
+mod allow_list;
+mod client;
+mod guard;
+
+use client::ClientSettings;
 use futures::executor::block_on;
+use guard::Guard;
 use http::HeaderMap;
-use reqwest::{Client, Url};
+use once_cell::sync::OnceCell;
+use reqwest::Url;
 use std::{str::FromStr, sync::Arc};
 use tokio::runtime::Handle;
 use wasi_outbound_http::*;
 
+pub use allow_list::AllowList;
+pub use client::ClientConfig;
 pub use wasi_outbound_http::add_to_linker;
 
 wit_bindgen_wasmtime::export!("wit/ephemeral/wasi-outbound-http.wit");
 
 /// A very simple implementation for outbound HTTP requests.
-#[derive(Default, Clone)]
+///
+/// The requests share a client, and its pool of connections, which is created
+/// on the first request and shared by the clones of the implementation.
+/// The client runs its connections on its own thread, rather than on the Tokio
+/// runtime the requests are sent from, so an implementation can be shared
+/// across runtimes.
+#[derive(Clone)]
 pub struct OutboundHttp {
-    /// List of hosts guest modules are allowed to make requests to.
-    pub allowed_hosts: Arc<Option<Vec<String>>>,
+    /// The checks of the destinations of requests.
+    guard: Guard,
+
+    /// The settings of the client.
+    settings: Arc<ClientSettings>,
+
+    /// The client shared by the requests, which is only created when first needed.
+    client: Arc<OnceCell<reqwest::blocking::Client>>,
+}
+
+/// Builder for an outbound HTTP implementation.
+pub struct OutboundHttpBuilder {
+    allowed_hosts: Option<Vec<String>>,
+    block_private_networks: bool,
+    client_config: ClientConfig,
+}
+
+impl OutboundHttpBuilder {
+    /// Set whether requests to private networks, such as the loopback, private,
+    /// and link-local networks, are blocked, unless the allow-list explicitly
+    /// allows their addresses, as a network or as the host of an entry.
+    /// The addresses destinations resolve to are checked when connecting, and
+    /// the allow-list is checked again for every redirect. Since a proxy would
+    /// resolve the destinations itself, proxies are not used in that case.
+    pub fn block_private_networks(mut self, enabled: bool) -> Self {
+        self.block_private_networks = enabled;
+        self
+    }
+
+    /// Set the configuration of the client shared by the requests, such as
+    /// its timeouts, pool of connections, proxy, and root certificates.
+    pub fn client_config(mut self, config: ClientConfig) -> Self {
+        self.client_config = config;
+        self
+    }
+
+    /// Create the outbound HTTP implementation, failing if an entry of the
+    /// allow-list, the proxy, or a root certificate is invalid.
+    pub fn build(self) -> anyhow::Result<OutboundHttp> {
+        let allowed = AllowList::parse(self.allowed_hosts.as_deref().unwrap_or_default())?;
+        let guard = Guard {
+            allowed: Arc::new(allowed),
+            block_private_networks: self.block_private_networks,
+        };
+        let settings = ClientSettings::new(self.client_config, guard.clone())?;
+
+        Ok(OutboundHttp {
+            guard,
+            settings: Arc::new(settings),
+            client: Arc::default(),
+        })
+    }
+}
+
+impl Default for OutboundHttp {
+    /// Create an instance that does not allow guest modules to send any request,
+    /// using the default client configuration.
+    fn default() -> Self {
+        Self::builder(None)
+            .build()
+            .expect("the default configuration should be valid")
+    }
 }
 
 impl OutboundHttp {
-    pub fn new(allowed_hosts: Option<Vec<String>>) -> Self {
-        let allowed_hosts = Arc::new(allowed_hosts);
-        Self { allowed_hosts }
+    /// Create a new instance, allowing requests to the destinations of the
+    /// given allow-list, whose entries are described in [`AllowList`].
+    /// If `None` is passed, guest modules are not allowed to send any request.
+    pub fn new(allowed_hosts: Option<Vec<String>>) -> anyhow::Result<Self> {
+        Self::builder(allowed_hosts).build()
     }
 
-    /// Check if guest module is allowed to send request to URL, based on the list of
-    /// allowed hosts defined by the runtime.
-    /// If `None` is passed, the guest module is not allowed to send the request.
-    fn is_allowed(url: &str, allowed_hosts: Arc<Option<Vec<String>>>) -> Result<bool, HttpError> {
-        let url_host = Url::parse(url)
-            .map_err(|_| HttpError::InvalidUrl)?
-            .host_str()
-            .ok_or(HttpError::InvalidUrl)?
-            .to_owned();
-        match allowed_hosts.as_deref() {
-            Some(domains) => {
-                let allowed: Result<Vec<_>, _> = domains.iter().map(|d| Url::parse(d)).collect();
-                let allowed = allowed.map_err(|_| HttpError::InvalidUrl)?;
-                let a: Vec<&str> = allowed.iter().map(|u| u.host_str().unwrap()).collect();
-                Ok(a.contains(&url_host.as_str()))
-            }
-            None => Ok(false),
+    /// Return a builder for an instance allowing requests to the destinations
+    /// of the given allow-list.
+    pub fn builder(allowed_hosts: Option<Vec<String>>) -> OutboundHttpBuilder {
+        OutboundHttpBuilder {
+            allowed_hosts,
+            block_private_networks: false,
+            client_config: ClientConfig::default(),
         }
     }
+
+    /// Return the destinations guest modules are allowed to send requests to.
+    pub fn allowed(&self) -> &AllowList {
+        &self.guard.allowed
+    }
+
+    /// Return the client shared by the requests, creating it on first use.
+    /// Since the client blocks while it is created, this must not be called
+    /// from an asynchronous context.
+    fn client(&self) -> reqwest::Result<&reqwest::blocking::Client> {
+        self.client.get_or_try_init(|| self.settings.client())
+    }
+
+    /// Send the request with the shared client and read its response.
+    /// Since the client blocks until the response is read, this must not be
+    /// called from an asynchronous context.
+    fn send(
+        &self,
+        method: http::Method,
+        url: Url,
+        headers: HeaderMap,
+        body: Vec<u8>,
+    ) -> Result<Response, HttpError> {
+        let res = self
+            .client()?
+            .request(method, url)
+            .headers(headers)
+            .body(body)
+            .send()?;
+        Response::try_from(res)
+    }
 }
 
 impl wasi_outbound_http::WasiOutboundHttp for OutboundHttp {
     fn request(&mut self, req: Request) -> Result<Response, HttpError> {
-        if !Self::is_allowed(&req.uri, self.allowed_hosts.clone())? {
+        let mut url = Url::parse(req.uri).map_err(|_| HttpError::InvalidUrl)?;
+        if !self.guard.is_allowed(&url) {
             return Err(HttpError::DestinationNotAllowed);
         }
 
         let method = http::Method::from(req.method);
-        let url = Url::parse(req.uri).map_err(|_| HttpError::InvalidUrl)?;
+        if !req.params.is_empty() {
+            // The parameters are appended to the query of the URI, if any.
+            url.query_pairs_mut().extend_pairs(req.params);
+        }
         let headers = headers(req.headers)?;
         let body = req.body.unwrap_or_default().to_vec();
 
-        // TODO (@radu-matei)
-        // Ensure all  HTTP request and response objects are handled properly (query parameters, headers).
-
         match Handle::try_current() {
-            // If running in a Tokio runtime, spawn a new blocking executor
-            // that will send the HTTP request, and block on its execution.
-            // This attempts to avoid any deadlocks from other operations
-            // already executing on the same executor (compared with just
-            // blocking on the current one).
-            Ok(r) => block_on(r.spawn_blocking(move || -> Result<Response, HttpError> {
-                let client = Client::builder().build().unwrap();
-                let res = block_on(
-                    client
-                        .request(method, url)
-                        .headers(headers)
-                        .body(body)
-                        .send(),
-                )?;
-
-                Ok(Response::try_from(res)?)
-            }))
-            .map_err(|_| HttpError::RuntimeError)?,
-            Err(_) => {
-                let res = reqwest::blocking::Client::new()
-                    .request(method, url)
-                    .headers(headers)
-                    .body(body)
-                    .send()?;
-                Ok(Response::try_from(res)?)
+            // If running in a Tokio runtime, send the HTTP request from a new
+            // blocking thread, and block on its execution. This attempts to
+            // avoid any deadlocks from other operations already executing on
+            // the same executor (compared with just blocking on the current one).
+            Ok(r) => {
+                let http = self.clone();
+                block_on(r.spawn_blocking(move || http.send(method, url, headers, body)))
+                    .map_err(|_| HttpError::RuntimeError)?
             }
+            Err(_) => self.send(method, url, headers, body),
         }
     }
 }
@@ -102,30 +185,12 @@ impl From<Method> for http::Method {
     }
 }
 
-impl TryFrom<reqwest::Response> for Response {
-    type Error = HttpError;
-
-    fn try_from(res: reqwest::Response) -> Result<Self, Self::Error> {
-        let status = res.status().as_u16();
-        // TODO (@radu-matei)
-        let headers = Some(Vec::new());
-        let body = Some(block_on(res.bytes())?.to_vec());
-
-        Ok(Response {
-            status,
-            headers,
-            body,
-        })
-    }
-}
-
 impl TryFrom<reqwest::blocking::Response> for Response {
     type Error = HttpError;
 
     fn try_from(res: reqwest::blocking::Response) -> Result<Self, Self::Error> {
         let status = res.status().as_u16();
-        // TODO (@radu-matei)
-        let headers = Some(Vec::new());
+        let headers = Some(response_headers(res.headers()));
         let body = Some(res.bytes()?.to_vec());
 
         Ok(Response {
@@ -136,17 +201,35 @@ impl TryFrom<reqwest::blocking::Response> for Response {
     }
 }
 
-fn headers(h: HeadersParam) -> anyhow::Result<HeaderMap> {
+/// Convert the headers of a request into a header map, keeping every value
+/// of the headers that are repeated.
+fn headers(h: HeadersParam) -> Result<HeaderMap, HttpError> {
     let mut res = HeaderMap::new();
     for (k, v) in h {
-        res.insert(
-            http::header::HeaderName::from_str(k)?,
-            http::header::HeaderValue::from_str(v)?,
+        res.append(
+            http::header::HeaderName::from_str(k).map_err(|_| HttpError::InvalidHeader)?,
+            http::header::HeaderValue::from_str(v).map_err(|_| HttpError::InvalidHeader)?,
         );
     }
     Ok(res)
 }
 
+/// Convert the headers of a response into a list of (name, value) pairs,
+/// with a pair for each value of the headers with multiple values.
+/// Header values are not necessarily valid UTF-8, so values that are not
+/// are decoded as ISO-8859-1, which maps each byte to a single character.
+fn response_headers(h: &HeaderMap) -> Vec<(String, String)> {
+    h.iter()
+        .map(|(k, v)| {
+            let v = match std::str::from_utf8(v.as_bytes()) {
+                Ok(v) => v.to_string(),
+                Err(_) => v.as_bytes().iter().map(|&b| b as char).collect(),
+            };
+            (k.as_str().to_string(), v)
+        })
+        .collect()
+}
+
 impl From<anyhow::Error> for HttpError {
     fn from(_: anyhow::Error) -> Self {
         Self::RuntimeError
@@ -154,7 +237,11 @@ impl From<anyhow::Error> for HttpError {
 }
 
 impl From<reqwest::Error> for HttpError {
-    fn from(_: reqwest::Error) -> Self {
-        Self::RequestError
+    fn from(e: reqwest::Error) -> Self {
+        if guard::is_not_allowed(&e) {
+            Self::DestinationNotAllowed
+        } else {
+            Self::RequestError
+        }
     }
 }
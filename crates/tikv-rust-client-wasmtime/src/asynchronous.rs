@@ -0,0 +1,363 @@
+//! Asynchronous implementation of the WASI cache interface using a TiKV client,
+//! for engines with async support enabled.
+
+use crate::{
+    decode, encode, error, prefix_end, TikvClientBuilder, MAX_INCREMENT_ATTEMPTS, MAX_SCAN_LIMIT,
+};
+use cache_wasi_wasmtime::{
+    asynchronous::wasi_cache::{self, *},
+    validate_namespace,
+};
+use std::{collections::HashMap, sync::Arc};
+use tikv_client::{BoundRange, RawClient};
+
+/// Asynchronous TiKV implementation for the WASI cache interface.
+///
+/// The client awaits TiKV on the runtime of the caller, so it can be used from
+/// inside a Tokio runtime, unlike the blocking client, which is built on it.
+#[derive(Clone)]
+pub struct AsyncTikvClient {
+    /// pd-endpoint for the TiKV client.
+    pub pd_endpoint: String,
+
+    /// the Tikv rust raw client (no transaction), in atomic mode so that
+    /// compare-and-swap operations are consistent with the other writes.
+    inner: Arc<RawClient>,
+
+    /// The namespace prepended to every key, if any.
+    namespace: Option<String>,
+
+    /// Whether the time-to-live of entries is passed to TiKV.
+    ttl: bool,
+}
+
+#[cache_wasi_wasmtime::async_trait]
+impl wasi_cache::WasiCache for AsyncTikvClient {
+    /// Set the payload for the given key.
+    /// The time-to-live argument (in seconds) is only used if TTL is enabled for the client.
+    async fn set(
+        &mut self,
+        key: &str,
+        value: PayloadParam<'_>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting key {}", key);
+        AsyncTikvClient::set(self, key, value, ttl)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Get the payload for the given key, if it exists.
+    async fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
+        log::info!("getting key {}", key);
+        AsyncTikvClient::get(self, key)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Delete the entry for the given key.
+    async fn delete(&mut self, key: &str) -> Result<(), Error> {
+        log::info!("deleting key {}", key);
+        AsyncTikvClient::delete(self, key)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Get the payloads for the given keys.
+    async fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        AsyncTikvClient::get_many(self, &keys)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Set the payloads for the given keys.
+    async fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        AsyncTikvClient::set_many(self, &entries, ttl)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Delete the entries for the given keys.
+    async fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        AsyncTikvClient::delete_many(self, &keys)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Increment the integer stored for the given key.
+    async fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        AsyncTikvClient::increment(self, key, delta)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// Replace the payload for the given key if it is equal to the old one.
+    async fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        AsyncTikvClient::compare_and_swap(self, key, old, value)
+            .await
+            .map_err(|e| error(e).into())
+    }
+
+    /// List a page of the keys starting with the given prefix.
+    async fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        let (keys, cursor) = AsyncTikvClient::list_keys(self, prefix, cursor, limit)
+            .await
+            .map_err(error)?;
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    async fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        let lifetime = AsyncTikvClient::get_ttl(self, key).await.map_err(error)?;
+        Ok(lifetime.map(Into::into))
+    }
+}
+
+impl AsyncTikvClient {
+    /// Connect to the TiKV cluster and create the client.
+    pub(crate) async fn connect(builder: TikvClientBuilder) -> anyhow::Result<Self> {
+        if let Some(ns) = &builder.namespace {
+            validate_namespace(ns)?;
+        }
+
+        let client = RawClient::new(vec![builder.pd_endpoint.as_str()], None)
+            .await?
+            .with_atomic_for_cas();
+        Ok(Self {
+            pd_endpoint: builder.pd_endpoint,
+            inner: Arc::new(client),
+            namespace: builder.namespace,
+            ttl: builder.ttl,
+        })
+    }
+
+    /// Return a clone of the client using the given namespace, which shares
+    /// the connection to the cluster of this client.
+    pub fn with_namespace(&self, namespace: &str) -> anyhow::Result<Self> {
+        validate_namespace(namespace)?;
+        Ok(Self {
+            namespace: Some(namespace.to_string()),
+            ..self.clone()
+        })
+    }
+
+    /// Return the namespace of the client, if any.
+    pub fn namespace(&self) -> Option<&str> {
+        self.namespace.as_deref()
+    }
+
+    /// Set the payload for the given key, with an optional time-to-live (in seconds)
+    /// if TTL is enabled for the client. TiKV never expires an entry with a zero
+    /// time-to-live, so such an entry, which expires immediately, is deleted instead.
+    pub async fn set(&self, key: &str, value: &[u8], ttl: Option<u32>) -> anyhow::Result<()> {
+        match self.ttl_secs(ttl) {
+            Some(0) => self.delete(key).await,
+            Some(s) => Ok(self
+                .inner
+                .put_with_ttl(self.key(key), encode(value), s)
+                .await?),
+            None => Ok(self.inner.put(self.key(key), encode(value)).await?),
+        }
+    }
+
+    pub async fn get(&self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
+        let value = self.inner.get(self.key(key)).await?;
+        Ok(value.map(decode))
+    }
+
+    pub async fn delete(&self, key: &str) -> anyhow::Result<()> {
+        Ok(self.inner.delete(self.key(key)).await?)
+    }
+
+    pub async fn get_many(&self, keys: &[&str]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        let pairs = self
+            .inner
+            .batch_get(keys.iter().map(|k| self.key(k)))
+            .await?;
+        // `batch_get` only returns the pairs that were found, in no particular order.
+        let values: HashMap<Vec<u8>, Vec<u8>> = pairs
+            .into_iter()
+            .map(|pair| (pair.0.into(), pair.1))
+            .collect();
+
+        Ok(keys
+            .iter()
+            .map(|k| values.get(self.key(k).as_bytes()).cloned().map(decode))
+            .collect())
+    }
+
+    pub async fn set_many(
+        &self,
+        entries: &[(&str, &[u8])],
+        ttl: Option<u32>,
+    ) -> anyhow::Result<()> {
+        let pairs = entries
+            .iter()
+            .map(|(key, value)| (self.key(key), encode(value)));
+        match self.ttl_secs(ttl) {
+            Some(0) => {
+                let keys: Vec<&str> = entries.iter().map(|(key, _)| *key).collect();
+                self.delete_many(&keys).await
+            }
+            Some(s) => {
+                let ttls = vec![s; entries.len()];
+                Ok(self.inner.batch_put_with_ttl(pairs, ttls).await?)
+            }
+            None => Ok(self.inner.batch_put(pairs).await?),
+        }
+    }
+
+    pub async fn delete_many(&self, keys: &[&str]) -> anyhow::Result<()> {
+        Ok(self
+            .inner
+            .batch_delete(keys.iter().map(|k| self.key(k)))
+            .await?)
+    }
+
+    /// TiKV has no native increment, so the new value is written with
+    /// compare-and-swap, retrying a limited number of times if the key was
+    /// concurrently modified. Since compare-and-swap does not support TTL,
+    /// the new entry does not expire.
+    pub async fn increment(&self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        for _ in 0..MAX_INCREMENT_ATTEMPTS {
+            let current = self.inner.get(self.key(key)).await?;
+            let value: i64 = match current.clone().map(decode) {
+                Some(v) => std::str::from_utf8(&v)?.parse()?,
+                None => 0,
+            };
+            let value = value
+                .checked_add(delta)
+                .ok_or_else(|| anyhow::anyhow!("increment of key {} overflows", key))?;
+
+            let (_, swapped) = self
+                .inner
+                .compare_and_swap(self.key(key), current, encode(value.to_string().as_bytes()))
+                .await?;
+            if swapped {
+                return Ok(value);
+            }
+        }
+
+        Err(crate::wasi_cache::Error::Timeout(format!(
+            "increment of key {} gave up after {} concurrent modifications",
+            key, MAX_INCREMENT_ATTEMPTS
+        ))
+        .into())
+    }
+
+    pub async fn compare_and_swap(
+        &self,
+        key: &str,
+        old: Option<&[u8]>,
+        value: &[u8],
+    ) -> anyhow::Result<bool> {
+        let (_, swapped) = self
+            .inner
+            .compare_and_swap(self.key(key), old.map(encode), encode(value))
+            .await?;
+        Ok(swapped)
+    }
+
+    /// List the keys starting with the given prefix, in order, by scanning
+    /// the range of keys sharing the prefix. The cursor of a page is the last
+    /// key in that page, and the next page starts right after it.
+    /// The namespace is removed from the keys.
+    pub async fn list_keys(
+        &self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> anyhow::Result<(Vec<String>, Option<String>)> {
+        let prefix = self.key(prefix);
+        let mut start = prefix.as_bytes().to_vec();
+        if let Some(c) = cursor {
+            // The smallest key greater than the cursor is the cursor followed by a zero byte.
+            let mut after = self.key(c).into_bytes();
+            after.push(0);
+            start = start.max(after);
+        }
+        let range: BoundRange = match prefix_end(prefix.as_bytes()) {
+            Some(end) => (start..end).into(),
+            None => (start..).into(),
+        };
+
+        let limit = limit.clamp(1, MAX_SCAN_LIMIT);
+        let keys = self
+            .inner
+            .scan_keys(range, limit)
+            .await?
+            .into_iter()
+            .map(|k| String::from_utf8(k.into()))
+            .collect::<Result<Vec<_>, _>>()?;
+        let keys: Vec<String> = match &self.namespace {
+            Some(ns) => keys
+                .iter()
+                .filter_map(|k| k.strip_prefix(ns.as_str())?.strip_prefix(':'))
+                .map(String::from)
+                .collect(),
+            None => keys,
+        };
+
+        let cursor = if keys.len() == limit as usize {
+            keys.last().cloned()
+        } else {
+            None
+        };
+        Ok((keys, cursor))
+    }
+
+    /// Return the remaining lifetime of the entry for the given key, if it exists.
+    /// If TTL is not enabled for the client, existing entries never expire.
+    pub async fn get_ttl(&self, key: &str) -> anyhow::Result<Option<crate::wasi_cache::Lifetime>> {
+        use crate::wasi_cache::Lifetime;
+
+        if !self.ttl {
+            let value = self.inner.get(self.key(key)).await?;
+            return Ok(value.map(|_| Lifetime::Persistent));
+        }
+
+        // TiKV returns no TTL for missing keys, and a zero TTL for entries that do not expire.
+        let ttl = self.inner.get_key_ttl_secs(self.key(key)).await?;
+        Ok(ttl.map(|s| match s {
+            0 => Lifetime::Persistent,
+            s => Lifetime::Remaining(s.min(u32::MAX as u64) as u32),
+        }))
+    }
+
+    /// Return the time-to-live (in seconds) to pass to TiKV, if TTL is enabled for the client.
+    fn ttl_secs(&self, ttl: Option<u32>) -> Option<u64> {
+        if !self.ttl {
+            return None;
+        }
+        ttl.map(u64::from)
+    }
+
+    /// Return the key stored in TiKV for the given key, prefixed with the namespace.
+    fn key(&self, key: &str) -> String {
+        match &self.namespace {
+            Some(ns) => format!("{}:{}", ns, key),
+            None => key.to_string(),
+        }
+    }
+}
@@ -1,80 +1,326 @@
-//! Impement the WASI cache interface using a TiKV client.
+//! Implement the WASI cache interface using a TiKV client.
 //! This is using a Wasmtime host implementation.
+//!
+//! Payloads are stored unchanged, except for the empty payload and the payloads
+//! starting with the `0xff` byte, which are stored with a leading `0xff` marker,
+//! since TiKV does not support empty values. Values written by earlier versions,
+//! which stored every payload unchanged, are read as they were written, unless
+//! they start with `0xff`, which never appears in UTF-8 text. Such binary values
+//! must be written again, or they are read without their first byte.
+
+mod asynchronous;
 
 use std::sync::Arc;
 use tokio::runtime::Runtime;
 use wasi_cache::*;
 
-pub use wasi_cache::add_to_linker;
-
-wit_bindgen_wasmtime::export!("wit/ephemeral/wasi-cache.wit");
+pub use asynchronous::AsyncTikvClient;
+pub use cache_wasi_wasmtime::asynchronous::wasi_cache::add_to_linker as add_to_linker_async;
+pub use cache_wasi_wasmtime::wasi_cache::{self, add_to_linker};
 
+/// Blocking TiKV implementation for the WASI cache interface.
+///
+/// The client runs the asynchronous client on its own Tokio runtime, so it must
+/// not be created, used, or dropped from inside another Tokio runtime, which
+/// should use the asynchronous client instead.
 #[derive(Clone)]
 pub struct TikvClient {
     /// pd-endpoint for the TiKV client.
     pub pd_endpoint: String,
 
-    /// the Tikv rust raw client (no transaction)
-    inner: Arc<tikv_client::RawClient>,
+    /// The asynchronous client the operations are run with.
+    client: AsyncTikvClient,
+
+    runtime: Arc<Runtime>,
+}
+
+/// Builder for a TiKV client.
+pub struct TikvClientBuilder {
+    pd_endpoint: String,
+    namespace: Option<String>,
+    ttl: bool,
+}
+
+impl TikvClientBuilder {
+    /// Set the namespace of the client, which isolates its keys from the keys
+    /// of clients using other namespaces. Clients without a namespace can access
+    /// the keys of every namespace.
+    pub fn namespace(mut self, namespace: &str) -> Self {
+        self.namespace = Some(namespace.to_string());
+        self
+    }
+
+    /// Set whether the time-to-live of entries is passed to TiKV, which is disabled
+    /// by default. Enabling it requires the cluster to have `storage.enable-ttl` set,
+    /// since TiKV rejects the writes with a time-to-live otherwise. When disabled,
+    /// the time-to-live is ignored and entries never expire.
+    pub fn ttl(mut self, enabled: bool) -> Self {
+        self.ttl = enabled;
+        self
+    }
 
-    runtime: Arc<tokio::runtime::Runtime>,
+    /// Connect to the TiKV cluster and create the client, with its own Tokio runtime.
+    pub fn build(self) -> anyhow::Result<TikvClient> {
+        let runtime = Runtime::new()?;
+        let client = runtime.block_on(self.build_async())?;
+        Ok(TikvClient {
+            pd_endpoint: client.pd_endpoint.clone(),
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// Connect to the TiKV cluster and create an asynchronous client, for
+    /// engines with async support enabled.
+    pub async fn build_async(self) -> anyhow::Result<AsyncTikvClient> {
+        AsyncTikvClient::connect(self).await
+    }
 }
 
 impl wasi_cache::WasiCache for TikvClient {
     /// Set the payload for the given key.
-    fn set(&mut self, key: &str, value: PayloadParam<'_>, _ttl: Option<u32>) -> Result<(), Error> {
+    /// The time-to-live argument (in seconds) is only used if TTL is enabled for the client.
+    fn set(&mut self, key: &str, value: PayloadParam<'_>, ttl: Option<u32>) -> Result<(), Error> {
         log::info!("setting key {}", key);
-        self.set(key, value)?;
-        Ok(())
+        self.set(key, value, ttl).map_err(error)
     }
 
-    /// Get the payload for the given key.
-    fn get(&mut self, key: &str) -> Result<PayloadResult, Error> {
+    /// Get the payload for the given key, if it exists.
+    fn get(&mut self, key: &str) -> Result<Option<PayloadResult>, Error> {
         log::info!("getting key {}", key);
-        let value = self.get(key)?;
-        match value {
-            Some(value) => Ok(value),
-            None => Ok(vec![]),
-        }
+        self.get(key).map_err(error)
     }
 
     /// Delete the entry for the given key.
     fn delete(&mut self, key: &str) -> Result<(), Error> {
         log::info!("deleting key {}", key);
-        Ok(self.delete(key)?)
+        self.delete(key).map_err(error)
+    }
+
+    /// Get the payloads for the given keys.
+    fn get_many(&mut self, keys: Vec<&str>) -> Result<Vec<Option<PayloadResult>>, Error> {
+        log::info!("getting {} keys", keys.len());
+        self.get_many(&keys).map_err(error)
+    }
+
+    /// Set the payloads for the given keys.
+    fn set_many(
+        &mut self,
+        entries: Vec<(&str, PayloadParam<'_>)>,
+        ttl: Option<u32>,
+    ) -> Result<(), Error> {
+        log::info!("setting {} keys", entries.len());
+        self.set_many(&entries, ttl).map_err(error)
+    }
+
+    /// Delete the entries for the given keys.
+    fn delete_many(&mut self, keys: Vec<&str>) -> Result<(), Error> {
+        log::info!("deleting {} keys", keys.len());
+        self.delete_many(&keys).map_err(error)
+    }
+
+    /// Increment the integer stored for the given key.
+    fn increment(&mut self, key: &str, delta: i64) -> Result<i64, Error> {
+        log::info!("incrementing key {}", key);
+        self.increment(key, delta).map_err(error)
+    }
+
+    /// Replace the payload for the given key if it is equal to the old one.
+    fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<PayloadParam<'_>>,
+        value: PayloadParam<'_>,
+    ) -> Result<bool, Error> {
+        log::info!("compare and swap key {}", key);
+        self.compare_and_swap(key, old, value).map_err(error)
+    }
+
+    /// List a page of the keys starting with the given prefix.
+    fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> Result<KeyPage, Error> {
+        log::info!("listing keys with prefix {}", prefix);
+        self.list_keys(prefix, cursor, limit).map_err(error)
+    }
+
+    /// Get the remaining lifetime of the entry for the given key, if it exists.
+    fn get_ttl(&mut self, key: &str) -> Result<Option<Lifetime>, Error> {
+        log::info!("getting the lifetime of key {}", key);
+        self.get_ttl(key).map_err(error)
     }
 }
 
 impl TikvClient {
     pub fn new(pd_endpoint: &str) -> anyhow::Result<Self> {
-        let mut rt = Runtime::new().unwrap();
-        let client: tikv_client::RawClient =
-            rt.block_on(tikv_client::RawClient::new(vec![pd_endpoint], None))?;
-        Ok(Self {
+        Self::builder(pd_endpoint).build()
+    }
+
+    /// Return a builder for a client of the TiKV cluster with the given PD endpoint.
+    pub fn builder(pd_endpoint: &str) -> TikvClientBuilder {
+        TikvClientBuilder {
             pd_endpoint: pd_endpoint.to_string(),
-            inner: Arc::new(client),
-            runtime: Arc::new(rt),
+            namespace: None,
+            ttl: false,
+        }
+    }
+
+    /// Return a clone of the client using the given namespace, which shares
+    /// the connection to the cluster and the runtime of this client.
+    pub fn with_namespace(&self, namespace: &str) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: self.client.with_namespace(namespace)?,
+            ..self.clone()
         })
     }
 
-    pub fn set(&mut self, key: &str, value: &[u8]) -> anyhow::Result<()> {
-        Ok(self
-            .runtime
-            .block_on(self.inner.put(key.to_string(), value))?)
+    /// Return the namespace of the client, if any.
+    pub fn namespace(&self) -> Option<&str> {
+        self.client.namespace()
+    }
+
+    /// Set the payload for the given key, with an optional time-to-live (in seconds)
+    /// if TTL is enabled for the client.
+    pub fn set(&mut self, key: &str, value: &[u8], ttl: Option<u32>) -> anyhow::Result<()> {
+        self.runtime.block_on(self.client.set(key, value, ttl))
     }
 
     pub fn get(&mut self, key: &str) -> anyhow::Result<Option<Vec<u8>>> {
-        let value = self.runtime.block_on(self.inner.get(key.to_string()))?;
-        Ok(value)
+        self.runtime.block_on(self.client.get(key))
     }
 
     pub fn delete(&mut self, key: &str) -> anyhow::Result<()> {
-        Ok(self.runtime.block_on(self.inner.delete(key.to_string()))?)
+        self.runtime.block_on(self.client.delete(key))
+    }
+
+    pub fn get_many(&mut self, keys: &[&str]) -> anyhow::Result<Vec<Option<Vec<u8>>>> {
+        self.runtime.block_on(self.client.get_many(keys))
+    }
+
+    pub fn set_many(&mut self, entries: &[(&str, &[u8])], ttl: Option<u32>) -> anyhow::Result<()> {
+        self.runtime.block_on(self.client.set_many(entries, ttl))
+    }
+
+    pub fn delete_many(&mut self, keys: &[&str]) -> anyhow::Result<()> {
+        self.runtime.block_on(self.client.delete_many(keys))
+    }
+
+    /// TiKV has no native increment, so the new value is written with
+    /// compare-and-swap, retrying a limited number of times if the key was
+    /// concurrently modified. Since compare-and-swap does not support TTL,
+    /// the new entry does not expire.
+    pub fn increment(&mut self, key: &str, delta: i64) -> anyhow::Result<i64> {
+        self.runtime.block_on(self.client.increment(key, delta))
+    }
+
+    pub fn compare_and_swap(
+        &mut self,
+        key: &str,
+        old: Option<&[u8]>,
+        value: &[u8],
+    ) -> anyhow::Result<bool> {
+        self.runtime
+            .block_on(self.client.compare_and_swap(key, old, value))
+    }
+
+    /// List the keys starting with the given prefix, in order, by scanning
+    /// the range of keys sharing the prefix. The cursor of a page is the last
+    /// key in that page, and the next page starts right after it.
+    /// The namespace is removed from the keys.
+    pub fn list_keys(
+        &mut self,
+        prefix: &str,
+        cursor: Option<&str>,
+        limit: u32,
+    ) -> anyhow::Result<KeyPage> {
+        let (keys, cursor) = self
+            .runtime
+            .block_on(self.client.list_keys(prefix, cursor, limit))?;
+        Ok(KeyPage { keys, cursor })
+    }
+
+    /// Return the remaining lifetime of the entry for the given key, if it exists.
+    /// If TTL is not enabled for the client, existing entries never expire.
+    pub fn get_ttl(&mut self, key: &str) -> anyhow::Result<Option<Lifetime>> {
+        self.runtime.block_on(self.client.get_ttl(key))
     }
 }
 
-impl From<anyhow::Error> for Error {
-    fn from(_: anyhow::Error) -> Self {
-        Self::Error
+/// The maximum number of keys TiKV returns from a single scan.
+const MAX_SCAN_LIMIT: u32 = 10240;
+
+/// The maximum number of compare-and-swap attempts of an increment, after
+/// which it fails with a timeout if the key is still concurrently modified.
+const MAX_INCREMENT_ATTEMPTS: usize = 64;
+
+/// Return the smallest key greater than every key starting with the given
+/// prefix, or `None` if there is no such key.
+fn prefix_end(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut end = prefix.to_vec();
+    while let Some(b) = end.pop() {
+        if b < u8::MAX {
+            end.push(b + 1);
+            return Some(end);
+        }
+    }
+    None
+}
+
+/// The marker byte of the values that are not stored unchanged.
+///
+/// TiKV does not support empty values, so the empty payload is stored as the
+/// marker byte alone, and payloads starting with the marker byte are stored
+/// with an extra one, which is removed when reading them. Every other payload
+/// is stored unchanged, as it was before the marker was introduced.
+const VALUE_MARKER: u8 = 0xff;
+
+/// Encode a payload into the value stored in TiKV.
+fn encode(payload: &[u8]) -> Vec<u8> {
+    if payload.first().is_some_and(|b| *b != VALUE_MARKER) {
+        return payload.to_vec();
+    }
+
+    let mut value = Vec::with_capacity(payload.len() + 1);
+    value.push(VALUE_MARKER);
+    value.extend_from_slice(payload);
+    value
+}
+
+/// Decode a value stored in TiKV into the original payload.
+fn decode(mut value: Vec<u8>) -> Vec<u8> {
+    if value.first() == Some(&VALUE_MARKER) {
+        value.remove(0);
+    }
+    value
+}
+
+/// Convert an error from the client into an error for the WASI cache interface.
+/// Errors of the interface returned by the client are passed through.
+fn error(e: anyhow::Error) -> Error {
+    let e = match e.downcast::<Error>() {
+        Ok(e) => return e,
+        Err(e) => e,
+    };
+    match e.downcast::<tikv_client::Error>() {
+        Ok(e) => tikv_error(e),
+        Err(e) => Error::Other(format!("{:#}", e)),
+    }
+}
+
+/// Convert a TiKV error into an error for the WASI cache interface.
+fn tikv_error(e: tikv_client::Error) -> Error {
+    let msg = e.to_string();
+    match e {
+        tikv_client::Error::Io(e) if e.kind() == std::io::ErrorKind::TimedOut => {
+            Error::Timeout(msg)
+        }
+        tikv_client::Error::Io(_)
+        | tikv_client::Error::Grpc(_)
+        | tikv_client::Error::RegionError(_)
+        | tikv_client::Error::LeaderNotFound { .. } => Error::BackendUnavailable(msg),
+        _ => Error::Other(msg),
     }
 }
@@ -21,6 +21,7 @@ mod nn_tests {
             wasi,
             runtime_data,
             test_data,
+            async_test_data: None,
         };
 
         let add_imports = |linker| {
@@ -49,17 +50,48 @@ mod nn_tests {
 mod http_tests {
     use super::runtime::*;
     use anyhow::Result;
-    use wasi_outbound_http_wasmtime::OutboundHttp;
+    use std::{
+        io::{Read, Write},
+        net::{TcpListener, TcpStream},
+        sync::{
+            atomic::{AtomicUsize, Ordering},
+            Arc, Mutex,
+        },
+        thread,
+        time::Duration,
+    };
+    use url::Url;
+    use wasi_outbound_http_wasmtime::{
+        wasi_outbound_http::{HttpError, Method, Request, Response, WasiOutboundHttp},
+        AllowList, ClientConfig, OutboundHttp,
+    };
     use wasmtime::Linker;
 
     const HTTP_RUST_TEST: &str =
         "tests/modules/http-rust-hello/target/wasm32-wasi/release/http_rust_hello.wasm";
 
+    /// A response with repeated headers, and header values that are not ASCII.
+    const HEADERS_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\n\
+        Content-Type: text/plain\r\n\
+        Set-Cookie: hobbit=frodo\r\n\
+        Set-Cookie: wizard=gandalf\r\n\
+        X-Utf8: Lothl\xc3\xb3rien\r\n\
+        X-Latin1: Lothl\xf3rien\r\n\
+        Content-Length: 7\r\n\
+        Connection: close\r\n\
+        \r\n\
+        Mellon!";
+
+    const EMPTY_RESPONSE: &[u8] = b"HTTP/1.1 204 No Content\r\nConnection: close\r\n\r\n";
+
+    /// A response keeping the connection open for more requests.
+    const KEEP_ALIVE_RESPONSE: &[u8] = b"HTTP/1.1 200 OK\r\nContent-Length: 7\r\n\r\nMellon!";
+
     #[test]
     fn test_http_allowed() -> Result<()> {
         let data = Some(OutboundHttp::new(Some(vec![
             "https://example.com".to_string()
-        ])));
+        ]))?);
 
         let add_imports = |linker: &mut Linker<Context<_>>| {
             wasi_outbound_http_wasmtime::add_to_linker(linker, |ctx| -> &mut OutboundHttp {
@@ -73,7 +105,7 @@ mod http_tests {
     #[test]
     #[should_panic]
     fn test_http_not_allowed() {
-        let data = Some(OutboundHttp::new(None));
+        let data = Some(OutboundHttp::new(None).unwrap());
 
         let add_imports = |linker: &mut Linker<Context<_>>| {
             wasi_outbound_http_wasmtime::add_to_linker(linker, |ctx| -> &mut OutboundHttp {
@@ -83,18 +115,550 @@ mod http_tests {
 
         exec(HTTP_RUST_TEST, data, add_imports).unwrap();
     }
+
+    #[test]
+    fn test_http_response_headers() -> Result<()> {
+        let server = HttpTestServer::new(HEADERS_RESPONSE)?;
+        let mut http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+
+        check_headers_response(get(&mut http, &server.address));
+        assert!(server.requests()[0].starts_with("GET / HTTP/1.1\r\n"));
+        Ok(())
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_response_headers_in_runtime() -> Result<()> {
+        let server = HttpTestServer::new(HEADERS_RESPONSE)?;
+        let mut http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+
+        check_headers_response(get(&mut http, &server.address));
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_request_params_and_headers() -> Result<()> {
+        let server = HttpTestServer::new(EMPTY_RESPONSE)?;
+        let mut http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+
+        let uri = format!("{}/shire?hobbits=4", server.address);
+        let req = Request {
+            method: Method::Get,
+            uri: &uri,
+            headers: &[("x-fellowship", "frodo"), ("x-fellowship", "sam")],
+            params: &[("destination", "mount doom"), ("ring", "one&only")],
+            body: None,
+        };
+        let res = http.request(req).expect("the request should succeed");
+        assert_eq!(204, res.status);
+
+        let head = &server.requests()[0];
+        assert!(head.starts_with(
+            "GET /shire?hobbits=4&destination=mount+doom&ring=one%26only HTTP/1.1\r\n"
+        ));
+        assert!(head.contains("x-fellowship: frodo\r\n"));
+        assert!(head.contains("x-fellowship: sam\r\n"));
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_request_invalid_header() -> Result<()> {
+        let server = HttpTestServer::new(EMPTY_RESPONSE)?;
+        let mut http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+
+        for headers in [[("x-fellowship", "frodo\n")], [("x fellowship", "frodo")]] {
+            let req = Request {
+                method: Method::Get,
+                uri: &server.address,
+                headers: &headers,
+                params: &[],
+                body: None,
+            };
+            assert!(matches!(http.request(req), Err(HttpError::InvalidHeader)));
+        }
+
+        assert!(server.requests().is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_allow_list() -> Result<()> {
+        let server = HttpTestServer::new(EMPTY_RESPONSE)?;
+        let allowed = format!("{}/shire", server.address);
+        let mut http = OutboundHttp::new(Some(vec![allowed]))?;
+
+        let uri = format!("{}/shire/bag-end", server.address);
+        assert_eq!(204, get(&mut http, &uri).status);
+        for uri in [
+            format!("{}/mordor", server.address),
+            format!("{}/shire", server.address.replace("http:", "https:")),
+            "http://127.0.0.2/shire".to_string(),
+        ] {
+            let req = Request {
+                method: Method::Get,
+                uri: &uri,
+                headers: &[],
+                params: &[],
+                body: None,
+            };
+            assert!(matches!(
+                http.request(req),
+                Err(HttpError::DestinationNotAllowed)
+            ));
+        }
+
+        let mut http = OutboundHttp::new(Some(vec!["127.0.0.0/8".to_string()]))?;
+        assert_eq!(204, get(&mut http, &server.address).status);
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_allow_list_patterns() -> Result<()> {
+        let allowed = AllowList::parse(&[
+            "https://example.com",
+            "*.fellowship.org:*",
+            "http://api.example.com:8080/v1/",
+            "10.0.0.0/8",
+        ])?;
+        let allows = |url: &str| allowed.allows(&Url::parse(url).unwrap());
+
+        assert!(allows("https://example.com/anything"));
+        assert!(!allows("http://example.com"));
+        assert!(!allows("https://example.com:8443"));
+        assert!(allows("https://frodo.fellowship.org:8443"));
+        assert!(allows("http://a.b.fellowship.org"));
+        assert!(!allows("https://fellowship.org"));
+        assert!(!allows("https://evilfellowship.org"));
+        assert!(allows("http://api.example.com:8080/v1"));
+        assert!(allows("http://api.example.com:8080/v1/users"));
+        assert!(!allows("http://api.example.com:8080/v10"));
+        assert!(!allows("http://api.example.com/v1"));
+        assert!(allows("http://10.1.2.3:8080"));
+        assert!(!allows("http://11.1.2.3"));
+
+        assert!(AllowList::allow_all().allows(&Url::parse("ftp://example.com")?));
+        assert!(AllowList::parse(&["*"])?.allows(&Url::parse("http://example.com:1234")?));
+        assert!(!AllowList::default().allows(&Url::parse("https://example.com")?));
+
+        for entry in [
+            "mailto:frodo@example.com",
+            "ftp://example.com",
+            "https://",
+            "https://ex*mple.com",
+            "https://example.com:99999",
+            "https://example.com/path?query",
+        ] {
+            let err = OutboundHttp::new(Some(vec![entry.to_string()])).err();
+            let err = err.expect("the entry should be invalid").to_string();
+            assert!(err.contains(entry), "unexpected error {}", err);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_allow_list_host_entries() -> Result<()> {
+        let server = HttpTestServer::new(EMPTY_RESPONSE)?;
+
+        // Entries that used to only restrict the host now also restrict the
+        // scheme and the port of the requests.
+        for entry in ["http://127.0.0.1", "https://127.0.0.1:*"] {
+            let mut http = OutboundHttp::new(Some(vec![entry.to_string()]))?;
+            let req = Request {
+                method: Method::Get,
+                uri: &server.address,
+                headers: &[],
+                params: &[],
+                body: None,
+            };
+            assert!(matches!(
+                http.request(req),
+                Err(HttpError::DestinationNotAllowed)
+            ));
+        }
+        assert!(server.requests().is_empty());
+
+        let mut http = OutboundHttp::new(Some(vec!["*://127.0.0.1:*".to_string()]))?;
+        assert_eq!(204, get(&mut http, &server.address).status);
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_block_private_ranges() -> Result<()> {
+        let mut http = OutboundHttp::builder(Some(vec!["*".to_string()]))
+            .block_private_networks(true)
+            .build()?;
+
+        // An address of every blocked range, which is rejected before connecting.
+        for (range, address) in [
+            ("0.0.0.0/8", "0.1.2.3"),
+            ("10.0.0.0/8", "10.0.0.1"),
+            ("100.64.0.0/10", "100.64.0.1"),
+            ("127.0.0.0/8", "127.0.0.1"),
+            ("169.254.0.0/16", "169.254.169.254"),
+            ("172.16.0.0/12", "172.16.0.1"),
+            ("192.0.0.0/24", "192.0.0.8"),
+            ("192.168.0.0/16", "192.168.1.1"),
+            ("198.18.0.0/15", "198.19.0.1"),
+            ("224.0.0.0/4", "224.0.0.1"),
+            ("240.0.0.0/4", "240.0.0.1"),
+            ("255.255.255.255/32", "255.255.255.255"),
+            ("::/128", "[::]"),
+            ("::1/128", "[::1]"),
+            ("::ffff:0:0/96", "[::ffff:10.0.0.1]"),
+            ("::/96", "[::10.0.0.1]"),
+            ("64:ff9b::/96", "[64:ff9b::a00:1]"),
+            ("2001::/32", "[2001:0:4136:e378:8000:63bf:80ff:fffe]"),
+            ("2002::/16", "[2002:c0a8:101::1]"),
+            ("fc00::/7", "[fd00::1]"),
+            ("fe80::/10", "[fe80::1]"),
+            ("fec0::/10", "[fec0::1]"),
+            ("ff00::/8", "[ff02::1]"),
+        ] {
+            let uri = format!("http://{}/", address);
+            let req = Request {
+                method: Method::Get,
+                uri: &uri,
+                headers: &[],
+                params: &[],
+                body: None,
+            };
+            assert!(
+                matches!(http.request(req), Err(HttpError::DestinationNotAllowed)),
+                "{} should be blocked",
+                range
+            );
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_block_private_networks() -> Result<()> {
+        let target = HttpTestServer::new(EMPTY_RESPONSE)?;
+        let redirect = format!(
+            "HTTP/1.1 302 Found\r\nLocation: {}/\r\nContent-Length: 0\r\nConnection: close\r\n\r\n",
+            target.address
+        );
+        let server = HttpTestServer::new(redirect.as_bytes())?;
+        let port = server.address.rsplit(':').next().unwrap();
+        let localhost = format!("http://localhost:{}", port);
+
+        // Without the guard, the redirect to a destination that is not allowed is followed.
+        let mut http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+        assert_eq!(204, get(&mut http, &server.address).status);
+        assert_eq!(1, target.requests().len());
+
+        // The address of the server is allowed, since it is the host of an entry,
+        // but the redirect to the target is not, and neither is a name resolving to it.
+        for (allowed, uri) in [
+            (server.address.clone(), &server.address),
+            (localhost.clone(), &localhost),
+        ] {
+            let mut http = OutboundHttp::builder(Some(vec![allowed]))
+                .block_private_networks(true)
+                .build()?;
+            let req = Request {
+                method: Method::Get,
+                uri,
+                headers: &[],
+                params: &[],
+                body: None,
+            };
+            assert!(matches!(
+                http.request(req),
+                Err(HttpError::DestinationNotAllowed)
+            ));
+        }
+        assert_eq!(2, server.requests().len());
+        assert_eq!(1, target.requests().len());
+
+        // Private addresses are blocked even if every destination is allowed.
+        let mut http = OutboundHttp::builder(Some(vec!["*".to_string()]))
+            .block_private_networks(true)
+            .build()?;
+        let req = Request {
+            method: Method::Get,
+            uri: &target.address,
+            headers: &[],
+            params: &[],
+            body: None,
+        };
+        assert!(matches!(
+            http.request(req),
+            Err(HttpError::DestinationNotAllowed)
+        ));
+
+        // Including IPv4 private addresses embedded in IPv6 addresses.
+        for uri in [
+            "http://[::ffff:127.0.0.1]/",
+            "http://[64:ff9b::a00:1]/",
+            "http://[2002:c0a8:101::1]/",
+        ] {
+            let req = Request {
+                method: Method::Get,
+                uri,
+                headers: &[],
+                params: &[],
+                body: None,
+            };
+            assert!(matches!(
+                http.request(req),
+                Err(HttpError::DestinationNotAllowed)
+            ));
+        }
+
+        // Proxies would resolve the destinations without the guard.
+        let config = ClientConfig {
+            proxy: Some("http://rivendell:3128".to_string()),
+            ..Default::default()
+        };
+        let res = OutboundHttp::builder(Some(vec!["*".to_string()]))
+            .block_private_networks(true)
+            .client_config(config)
+            .build();
+        assert!(res.is_err());
+
+        // Unless they are explicitly allowed.
+        let mut http = OutboundHttp::builder(Some(vec!["127.0.0.0/8".to_string()]))
+            .block_private_networks(true)
+            .build()?;
+        assert_eq!(204, get(&mut http, &server.address).status);
+        assert_eq!(2, target.requests().len());
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_client_reuse() -> Result<()> {
+        check_client_reuse()
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_http_client_reuse_in_runtime() -> Result<()> {
+        check_client_reuse()
+    }
+
+    #[test]
+    fn test_http_client_across_runtimes() -> Result<()> {
+        let server = HttpTestServer::new(KEEP_ALIVE_RESPONSE)?;
+        let http = OutboundHttp::new(Some(vec![server.address.clone()]))?;
+
+        // The pooled connection should outlive the runtime it was opened from.
+        for _ in 0..2 {
+            let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+            let mut http = http.clone();
+            let res = runtime.block_on(async { get(&mut http, &server.address) });
+            assert_eq!(200, res.status);
+        }
+        assert_eq!(1, server.connections());
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_default() {
+        let req = Request {
+            method: Method::Get,
+            uri: "https://middle-earth.example",
+            headers: &[],
+            params: &[],
+            body: None,
+        };
+        assert!(matches!(
+            OutboundHttp::default().request(req),
+            Err(HttpError::DestinationNotAllowed)
+        ));
+    }
+
+    #[test]
+    fn test_http_client_timeout() -> Result<()> {
+        // The listener accepts connections, but never answers requests.
+        let listener = TcpListener::bind("127.0.0.1:0")?;
+        let address = format!("http://{}", listener.local_addr()?);
+        let config = ClientConfig {
+            timeout: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let mut http = OutboundHttp::builder(Some(vec![address.clone()]))
+            .client_config(config)
+            .build()?;
+
+        let req = Request {
+            method: Method::Get,
+            uri: &address,
+            headers: &[],
+            params: &[],
+            body: None,
+        };
+        assert!(matches!(http.request(req), Err(HttpError::RequestError)));
+        Ok(())
+    }
+
+    #[test]
+    fn test_http_client_invalid_config() {
+        let configs = [
+            ClientConfig {
+                proxy: Some("http://mordor:one-does-not-simply".to_string()),
+                ..Default::default()
+            },
+            ClientConfig {
+                root_certificates: vec![b"not a certificate".to_vec()],
+                ..Default::default()
+            },
+        ];
+        for config in configs {
+            let res = OutboundHttp::builder(Some(vec!["*".to_string()]))
+                .client_config(config.clone())
+                .build();
+            assert!(res.is_err(), "{:?} should be invalid", config);
+        }
+    }
+
+    /// Send several requests with the same instance, and its clones, which
+    /// should share a connection and the configured user agent.
+    fn check_client_reuse() -> Result<()> {
+        let server = HttpTestServer::new(KEEP_ALIVE_RESPONSE)?;
+        let config = ClientConfig {
+            user_agent: Some("bilbo/0.1".to_string()),
+            ..Default::default()
+        };
+        let mut http = OutboundHttp::builder(Some(vec![server.address.clone()]))
+            .client_config(config)
+            .build()?;
+
+        for _ in 0..2 {
+            assert_eq!(200, get(&mut http, &server.address).status);
+        }
+        assert_eq!(200, get(&mut http.clone(), &server.address).status);
+
+        let requests = server.requests();
+        assert_eq!(3, requests.len());
+        for req in requests {
+            assert!(req.contains("user-agent: bilbo/0.1\r\n"), "{}", req);
+        }
+        assert_eq!(1, server.connections());
+        Ok(())
+    }
+
+    fn check_headers_response(res: Response) {
+        let headers = res.headers.expect("the response should have headers");
+        let values = |name: &str| {
+            headers
+                .iter()
+                .filter(|(k, _)| k == name)
+                .map(|(_, v)| v.as_str())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(200, res.status);
+        assert_eq!(Some(b"Mellon!".to_vec()), res.body);
+        assert_eq!(vec!["text/plain"], values("content-type"));
+        assert_eq!(vec!["hobbit=frodo", "wizard=gandalf"], values("set-cookie"));
+        assert_eq!(vec!["Lothlórien"], values("x-utf8"));
+        assert_eq!(vec!["Lothlórien"], values("x-latin1"));
+    }
+
+    /// Send a GET request for the given URI, which should succeed.
+    fn get(http: &mut OutboundHttp, uri: &str) -> Response {
+        let req = Request {
+            method: Method::Get,
+            uri,
+            headers: &[],
+            params: &[],
+            body: None,
+        };
+        http.request(req).expect("the request should succeed")
+    }
+
+    /// A local HTTP server sending the same response to every request,
+    /// which records the head of the requests it received, and the number
+    /// of connections they were sent on. Connections are kept open for
+    /// more requests, unless the response closes them.
+    pub struct HttpTestServer {
+        pub address: String,
+        requests: Arc<Mutex<Vec<String>>>,
+        connections: Arc<AtomicUsize>,
+    }
+
+    impl HttpTestServer {
+        pub fn new(response: &[u8]) -> Result<Self> {
+            let response: Arc<[u8]> = response.into();
+            let listener = TcpListener::bind("127.0.0.1:0")?;
+            let address = format!("http://{}", listener.local_addr()?);
+            let requests = Arc::new(Mutex::new(Vec::new()));
+            let connections = Arc::new(AtomicUsize::new(0));
+
+            let (received, accepted) = (requests.clone(), connections.clone());
+            thread::spawn(move || {
+                for stream in listener.incoming().flatten() {
+                    accepted.fetch_add(1, Ordering::SeqCst);
+                    let (response, received) = (response.clone(), received.clone());
+                    thread::spawn(move || Self::serve(stream, &response, &received));
+                }
+            });
+
+            Ok(Self {
+                address,
+                requests,
+                connections,
+            })
+        }
+
+        /// Answer the requests sent on a connection, which have no body.
+        fn serve(mut stream: TcpStream, response: &[u8], received: &Mutex<Vec<String>>) {
+            let close = response.windows(17).any(|w| w == b"Connection: close");
+            let mut pending = Vec::new();
+            let mut buf = [0; 1024];
+            loop {
+                while let Some(i) = pending.windows(4).position(|w| w == b"\r\n\r\n") {
+                    let head = pending.drain(..i + 4).collect::<Vec<_>>();
+                    received
+                        .lock()
+                        .unwrap()
+                        .push(String::from_utf8_lossy(&head).into_owned());
+                    if stream.write_all(response).is_err() || close {
+                        return;
+                    }
+                }
+                match stream.read(&mut buf) {
+                    Ok(0) | Err(_) => return,
+                    Ok(n) => pending.extend_from_slice(&buf[..n]),
+                }
+            }
+        }
+
+        /// Return the head of the requests received so far.
+        pub fn requests(&self) -> Vec<String> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        /// Return the number of connections accepted so far.
+        pub fn connections(&self) -> usize {
+            self.connections.load(Ordering::SeqCst)
+        }
+    }
 }
 
 #[cfg(test)]
 mod cache_tests {
     use super::runtime::*;
     use anyhow::Result;
-    use cache_wasi_redis_wasmtime::RedisCache;
+    use cache_wasi_memory_wasmtime::MemoryCache;
+    use cache_wasi_redis_wasmtime::{
+        wasi_cache::{Error, WasiCache},
+        AsyncRedisCache, RedisCache,
+    };
+    use cache_wasi_sled_wasmtime::SledCache;
+    use cache_wasi_wasmtime::{Policy, TieredCache, TieredConfig};
     use std::{
+        io,
         net::{Ipv4Addr, SocketAddrV4, TcpListener},
         process::{Child, Command},
+        sync::{
+            atomic::{AtomicBool, Ordering},
+            Arc,
+        },
+        thread,
+        time::Duration,
     };
-    use tikv_rust_client_wasmtime::TikvClient;
+    use tikv_rust_client_wasmtime::{wasi_cache::Lifetime, AsyncTikvClient, TikvClient};
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
     use wasmtime::Linker;
 
     const REDIS_SERVER_CLI: &str = "redis-server";
@@ -105,35 +669,501 @@ mod cache_tests {
         "tests/modules/cache-rust/target/wasm32-wasi/release/cache_rust_linked_fs.wasm";
     const CACHE_CPP_TEST: &str = "tests/modules/cache-cpp/ctest.wasm";
     const CACHE_CPP_LINKED_FS_TEST: &str = "tests/modules/cache-cpp/ctest-fs-linked.wasm";
+    const CACHE_EVICTION_LINKED_FS_TEST: &str =
+        "tests/modules/cache-eviction/target/wasm32-wasi/release/cache_eviction_linked_fs.wasm";
 
     #[tokio::test]
     async fn test_redis_get_set_delete() -> Result<()> {
         init();
 
         let redis = RedisTestController::new().await?;
-        let data = Some(RedisCache::new(&redis.address)?);
+        let cache = RedisCache::new(&redis.address)?;
+        let data = Some(cache.clone());
         let add_imports = |linker: &mut Linker<Context<_>>| {
             cache_wasi_redis_wasmtime::add_to_linker(linker, |ctx| -> &mut RedisCache {
                 ctx.runtime_data.as_mut().unwrap()
             })
         };
 
+        exec(CACHE_CPP_TEST, data.clone(), add_imports)?;
+        exec(CACHE_RUST_TEST, data, add_imports)?;
+        exec(
+            CACHE_RUST_TEST,
+            Some(cache.with_namespace("tenant")?),
+            add_imports,
+        )?;
+
+        // The clones share a single pooled connection, since the modules run sequentially.
+        let metrics = cache.metrics();
+        assert_eq!(1, metrics.connects);
+        assert_eq!(0, metrics.connect_failures);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_dropped_connections() -> Result<()> {
+        init();
+
+        let redis = RedisTestController::new().await?;
+        let proxy = RedisResetProxy::new(&redis.address)?;
+        let mut cache = RedisCache::new(&proxy.address)?;
+        WasiCache::set(&mut cache, "palantir", b"1", None)?;
+
+        // The increment is applied by Redis, but its connection is dropped
+        // before the reply, so it fails instead of being applied twice.
+        proxy.reset_next_reply();
+        assert!(matches!(
+            WasiCache::increment(&mut cache, "palantir", 1),
+            Err(Error::BackendUnavailable(_))
+        ));
+        assert_eq!(0, cache.metrics().reconnects);
+
+        // Reads are safe to repeat, so they are retried on a new connection.
+        proxy.reset_next_reply();
+        assert_eq!(Some(b"2".to_vec()), WasiCache::get(&mut cache, "palantir")?);
+        assert_eq!(1, cache.metrics().reconnects);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_get_set_delete() -> Result<()> {
+        init();
+
+        let data = Some(MemoryCache::builder().max_entries(100).build()?);
+        let add_imports = |linker: &mut Linker<Context<_>>| {
+            cache_wasi_memory_wasmtime::add_to_linker(linker, |ctx| -> &mut MemoryCache {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
         exec(CACHE_CPP_TEST, data.clone(), add_imports)?;
         exec(CACHE_RUST_TEST, data, add_imports)
     }
 
+    #[test]
+    fn test_memory_ttl_expiry() -> Result<()> {
+        init();
+
+        let mut cache = MemoryCache::new();
+        WasiCache::set(&mut cache, "ithil", b"moon", Some(1))?;
+        WasiCache::set(&mut cache, "anor", b"sun", None)?;
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "ithil")?,
+            Some(Lifetime::Remaining(1))
+        ));
+
+        thread::sleep(Duration::from_millis(1500));
+        assert_eq!(None, WasiCache::get(&mut cache, "ithil")?);
+        assert!(WasiCache::get_ttl(&mut cache, "ithil")?.is_none());
+        assert_eq!(Some(b"sun".to_vec()), WasiCache::get(&mut cache, "anor")?);
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "anor")?,
+            Some(Lifetime::Persistent)
+        ));
+
+        // Expired entries are not listed.
+        let page = WasiCache::list_keys(&mut cache, "", None, 10)?;
+        assert_eq!(vec!["anor".to_string()], page.keys);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_eviction() -> Result<()> {
+        init();
+
+        // Reading an entry makes it the most recently used one.
+        let mut cache = MemoryCache::builder().max_entries(2).build()?;
+        WasiCache::set(&mut cache, "frodo", b"1", None)?;
+        WasiCache::set(&mut cache, "sam", b"2", None)?;
+        WasiCache::get(&mut cache, "frodo")?;
+        WasiCache::set(&mut cache, "merry", b"3", None)?;
+        assert_eq!(None, WasiCache::get(&mut cache, "sam")?);
+        assert_eq!(Some(b"1".to_vec()), WasiCache::get(&mut cache, "frodo")?);
+        assert_eq!(Some(b"3".to_vec()), WasiCache::get(&mut cache, "merry")?);
+
+        // The size of an entry is the size of its key and of its payload.
+        let mut cache = MemoryCache::builder().max_bytes(16).build()?;
+        WasiCache::set(&mut cache, "ent", b"12345", None)?;
+        WasiCache::set(&mut cache, "orc", b"12345", None)?;
+        WasiCache::set(&mut cache, "elf", b"12", None)?;
+        assert_eq!(None, WasiCache::get(&mut cache, "ent")?);
+        assert_eq!(Some(b"12345".to_vec()), WasiCache::get(&mut cache, "orc")?);
+        assert_eq!(Some(b"12".to_vec()), WasiCache::get(&mut cache, "elf")?);
+
+        // An entry larger than the cache is rejected without evicting anything.
+        assert!(matches!(
+            WasiCache::set(&mut cache, "balrog", &[0; 16], None),
+            Err(Error::ValueTooLarge(_))
+        ));
+        assert_eq!(Some(b"12345".to_vec()), WasiCache::get(&mut cache, "orc")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_memory_snapshot() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-memory-snapshot");
+        let _ = std::fs::remove_file(&path);
+
+        // The snapshot is saved when the last clone of the cache is dropped.
+        {
+            let mut cache = MemoryCache::builder().snapshot(&path).build()?;
+            let clone = cache.clone();
+            WasiCache::set(&mut cache, "gandalf", b"grey", None)?;
+            WasiCache::set(&mut cache, "saruman", b"white", Some(1))?;
+            WasiCache::set(&mut cache, "radagast", b"brown", Some(60))?;
+            WasiCache::get(&mut cache, "gandalf")?;
+            drop(clone);
+        }
+        assert_eq!(b"WCACHE01", &std::fs::read(&path)?[..8]);
+
+        // Expired entries are skipped when loading the snapshot.
+        thread::sleep(Duration::from_millis(2000));
+        let mut cache = MemoryCache::builder().snapshot(&path).build()?;
+        assert_eq!(
+            Some(b"grey".to_vec()),
+            WasiCache::get(&mut cache, "gandalf")?
+        );
+        assert_eq!(None, WasiCache::get(&mut cache, "saruman")?);
+        assert_eq!(
+            Some(b"brown".to_vec()),
+            WasiCache::get(&mut cache, "radagast")?
+        );
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "radagast")?,
+            Some(Lifetime::Remaining(s)) if s > 0 && s <= 60
+        ));
+
+        // Entries are saved from the least to the most recently used, so the
+        // most recently used ones are kept when loading into a smaller cache.
+        WasiCache::get(&mut cache, "gandalf")?;
+        cache.save_snapshot()?;
+        let mut small = MemoryCache::builder()
+            .max_entries(1)
+            .snapshot(&path)
+            .build()?;
+        assert_eq!(
+            Some(b"grey".to_vec()),
+            WasiCache::get(&mut small, "gandalf")?
+        );
+        assert_eq!(None, WasiCache::get(&mut small, "radagast")?);
+        drop(small);
+        drop(cache);
+
+        // A file that is not a snapshot is not loaded.
+        std::fs::write(&path, b"WCACHE99")?;
+        assert!(MemoryCache::builder().snapshot(&path).build().is_err());
+        std::fs::remove_file(&path)?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_sled_get_set_delete() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-sled-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let data = Some(SledCache::open(&path)?);
+        let add_imports = |linker: &mut Linker<Context<_>>| {
+            cache_wasi_sled_wasmtime::add_to_linker(linker, |ctx| -> &mut SledCache {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
+        exec(CACHE_CPP_TEST, data.clone(), add_imports)?;
+        exec(CACHE_RUST_TEST, data, add_imports)
+    }
+
+    #[test]
+    fn test_sled_ttl_expiry() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-sled-ttl-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let mut cache = SledCache::open(&path)?;
+        WasiCache::set(&mut cache, "ithil", b"moon", Some(1))?;
+        WasiCache::set(&mut cache, "anor", b"sun", None)?;
+        WasiCache::set(&mut cache, "rings", b"19", Some(1))?;
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "ithil")?,
+            Some(Lifetime::Remaining(1))
+        ));
+
+        // Expiration times are stored in seconds, so the entries may live up to a second longer.
+        thread::sleep(Duration::from_millis(2100));
+        assert_eq!(None, WasiCache::get(&mut cache, "ithil")?);
+        assert!(WasiCache::get_ttl(&mut cache, "ithil")?.is_none());
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "anor")?,
+            Some(Lifetime::Persistent)
+        ));
+        let page = WasiCache::list_keys(&mut cache, "", None, 10)?;
+        assert_eq!(vec!["anor".to_string()], page.keys);
+
+        // An expired integer is incremented from zero, and no longer expires.
+        assert_eq!(1, WasiCache::increment(&mut cache, "rings", 1)?);
+        assert!(matches!(
+            WasiCache::get_ttl(&mut cache, "rings")?,
+            Some(Lifetime::Persistent)
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_sled_sweeper() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-sled-sweeper-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let mut swept = SledCache::builder(&path)
+            .sweep_interval(Duration::from_millis(100))
+            .build()?;
+        let path = std::env::temp_dir().join("wasi-cache-sled-manual-sweep-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let mut cache = SledCache::builder(&path)
+            .sweep_interval(Duration::from_secs(3600))
+            .build()?;
+
+        for c in [&mut swept, &mut cache] {
+            WasiCache::set_many(c, vec![("nazgul", b"9"), ("ents", b"3")], Some(1))?;
+            WasiCache::set(c, "wizards", b"5", None)?;
+            // The entry no longer expires, so it must not be swept.
+            WasiCache::set(c, "ents", b"3", None)?;
+        }
+        thread::sleep(Duration::from_millis(2500));
+
+        // The background sweeper already removed the expired entry.
+        assert_eq!(0, swept.sweep()?);
+        assert_eq!(1, cache.sweep()?);
+        assert_eq!(0, cache.sweep()?);
+        for c in [&mut swept, &mut cache] {
+            assert_eq!(None, WasiCache::get(c, "nazgul")?);
+            assert_eq!(Some(b"3".to_vec()), WasiCache::get(c, "ents")?);
+            assert_eq!(Some(b"5".to_vec()), WasiCache::get(c, "wizards")?);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_sled_invalid_entries() -> Result<()> {
+        init();
+
+        // Write an entry and an index key too short to hold an expiration time.
+        let path = std::env::temp_dir().join("wasi-cache-sled-invalid-test");
+        let _ = std::fs::remove_dir_all(&path);
+        {
+            let db = sled::open(&path)?;
+            db.open_tree("entries")?
+                .insert("gollum", b"ring".to_vec())?;
+            db.open_tree("expiry")?.insert(b"\0", Vec::new())?;
+            db.flush()?;
+        }
+
+        let mut cache = SledCache::open(&path)?;
+        assert_eq!(None, WasiCache::get(&mut cache, "gollum")?);
+        assert!(WasiCache::get_ttl(&mut cache, "gollum")?.is_none());
+        assert!(WasiCache::list_keys(&mut cache, "", None, 10)?
+            .keys
+            .is_empty());
+        assert_eq!(0, cache.sweep()?);
+        assert_eq!(1, WasiCache::increment(&mut cache, "gollum", 1)?);
+        WasiCache::delete(&mut cache, "gollum")?;
+        assert_eq!(None, WasiCache::get(&mut cache, "gollum")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiered_get_set_delete() -> Result<()> {
+        init();
+
+        type Tiered = TieredCache<MemoryCache, SledCache>;
+
+        let path = std::env::temp_dir().join("wasi-cache-tiered-test");
+        let _ = std::fs::remove_dir_all(&path);
+        let l2 = SledCache::open(&path)?;
+        for policy in [Policy::ReadThrough, Policy::WriteThrough, Policy::WriteBack] {
+            let config = TieredConfig {
+                policy,
+                ..Default::default()
+            };
+            let data = Some(TieredCache::new(MemoryCache::new(), l2.clone(), config));
+            let add_imports = |linker: &mut Linker<Context<_>>| {
+                cache_wasi_wasmtime::wasi_cache::add_to_linker(linker, |ctx| -> &mut Tiered {
+                    ctx.runtime_data.as_mut().unwrap()
+                })
+            };
+
+            exec(CACHE_CPP_TEST, data.clone(), add_imports)?;
+            exec(CACHE_RUST_TEST, data, add_imports)?;
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiered_read_through_lifetime() -> Result<()> {
+        init();
+
+        let mut l1 = MemoryCache::new();
+        let config = TieredConfig {
+            policy: Policy::ReadThrough,
+            ..Default::default()
+        };
+        let mut cache = TieredCache::new(l1.clone(), MemoryCache::new(), config);
+
+        // Entries read from the L2 are stored in the L1 with the lifetime they have left.
+        WasiCache::set(&mut cache, "shadowfax", b"fast", Some(1))?;
+        assert_eq!(
+            Some(b"fast".to_vec()),
+            WasiCache::get(&mut cache, "shadowfax")?
+        );
+        assert!(matches!(
+            WasiCache::get_ttl(&mut l1, "shadowfax")?,
+            Some(Lifetime::Remaining(1))
+        ));
+
+        thread::sleep(Duration::from_millis(1500));
+        assert_eq!(None, WasiCache::get(&mut cache, "shadowfax")?);
+        Ok(())
+    }
+
+    #[test]
+    fn test_tiered_write_back_flush() -> Result<()> {
+        init();
+
+        let mut l2 = MemoryCache::new();
+        let config = TieredConfig {
+            policy: Policy::WriteBack,
+            max_pending_age: Some(Duration::from_millis(200)),
+            ..Default::default()
+        };
+        let mut cache = TieredCache::new(MemoryCache::new(), l2.clone(), config);
+
+        // Buffered writes are flushed by the first operation once they are old enough.
+        WasiCache::set(&mut cache, "frodo", b"ring-bearer", None)?;
+        drop(cache.clone());
+        assert_eq!(None, WasiCache::get(&mut l2, "frodo")?);
+        thread::sleep(Duration::from_millis(300));
+        assert_eq!(None, WasiCache::get(&mut cache, "sam")?);
+        assert_eq!(
+            Some(b"ring-bearer".to_vec()),
+            WasiCache::get(&mut l2, "frodo")?
+        );
+
+        // And when the last clone of the cache is dropped.
+        WasiCache::set(&mut cache, "sam", b"gardener", None)?;
+        drop(cache);
+        assert_eq!(Some(b"gardener".to_vec()), WasiCache::get(&mut l2, "sam")?);
+        Ok(())
+    }
+
     #[test]
     fn test_tikv_get_set() -> Result<()> {
         init();
 
-        let data = Some(TikvClient::new("127.0.0.1:2379")?);
+        let mut client = TikvClient::builder("127.0.0.1:2379").ttl(true).build()?;
+        let data = Some(client.clone());
         let add_imports = |linker: &mut Linker<Context<_>>| {
             tikv_rust_client_wasmtime::add_to_linker(linker, |ctx| -> &mut TikvClient {
                 ctx.runtime_data.as_mut().unwrap()
             })
         };
 
-        exec(CACHE_RUST_TEST, data, add_imports)
+        exec(CACHE_RUST_TEST, data, add_imports)?;
+
+        let key = "you_shall_not_pass";
+        client.set(key, b"Balrog", Some(60))?;
+        match client.get_ttl(key)? {
+            Some(Lifetime::Remaining(secs)) => assert!(secs > 0 && secs <= 60),
+            other => panic!("unexpected lifetime {:?}", other),
+        }
+        client.delete(key)?;
+        assert!(client.get_ttl(key)?.is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_async_get_set_delete() -> Result<()> {
+        init();
+
+        let redis = RedisTestController::new().await?;
+        let cache = RedisCache::builder(&redis.address).build_async().await?;
+        let add_imports = |linker: &mut Linker<Context<_>>| {
+            cache_wasi_redis_wasmtime::add_to_linker_async(linker, |ctx| -> &mut AsyncRedisCache {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
+        exec_async(CACHE_CPP_TEST, Some(cache.clone()), add_imports).await?;
+        exec_async(CACHE_RUST_TEST, Some(cache.clone()), add_imports).await?;
+        exec_async(
+            CACHE_RUST_TEST,
+            Some(cache.with_namespace("tenant")?),
+            add_imports,
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_tikv_async_get_set() -> Result<()> {
+        init();
+
+        // The client uses the runtime of the test, instead of blocking on its own.
+        let client = TikvClient::builder("127.0.0.1:2379")
+            .ttl(true)
+            .build_async()
+            .await?;
+        let add_imports = |linker: &mut Linker<Context<_>>| {
+            tikv_rust_client_wasmtime::add_to_linker_async(linker, |ctx| -> &mut AsyncTikvClient {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
+        exec_async(CACHE_RUST_TEST, Some(client.clone()), add_imports).await?;
+
+        let key = "you_shall_not_pass_async";
+        client.set(key, b"Balrog", Some(60)).await?;
+        assert!(matches!(
+            client.get_ttl(key).await?,
+            Some(Lifetime::Remaining(_))
+        ));
+        client.delete(key).await?;
+        assert_eq!(None, client.get(key).await?);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_tikv_unmarked_values() -> Result<()> {
+        init();
+
+        // Values written before the marker byte was introduced are stored
+        // unchanged, and must still be read as they were written.
+        let raw = tikv_client::RawClient::new(vec!["127.0.0.1:2379"], None).await?;
+        raw.put("legacy:mellon".to_string(), b"Friend".to_vec())
+            .await?;
+
+        let client = TikvClient::builder("127.0.0.1:2379")
+            .namespace("legacy")
+            .build_async()
+            .await?;
+        assert_eq!(Some(b"Friend".to_vec()), client.get("mellon").await?);
+
+        // The empty payload and the payloads starting with the marker byte
+        // are the only ones stored with the marker.
+        client.set("empty", b"", None).await?;
+        assert_eq!(Some(Vec::new()), client.get("empty").await?);
+        client.set("marked", &[0xff, 0x00], None).await?;
+        assert_eq!(Some(vec![0xff, 0x00]), client.get("marked").await?);
+        assert_eq!(
+            Some(vec![0xff, 0xff, 0x00]),
+            raw.get("legacy:marked".to_string()).await?
+        );
+
+        client.delete_many(&["mellon", "empty", "marked"]).await?;
+        Ok(())
     }
 
     #[test]
@@ -152,6 +1182,15 @@ mod cache_tests {
         exec_with_default_imports(CACHE_CPP_LINKED_FS_TEST, data).unwrap();
     }
 
+    #[test]
+    fn test_fs_eviction() -> Result<()> {
+        init();
+
+        let env = [("CACHE_FS_MAX_ENTRIES", "4"), ("MAX_ENTRIES", "4")];
+        let data: Option<u32> = None;
+        exec_with_env(CACHE_EVICTION_LINKED_FS_TEST, data, &env, |_| Ok(()))
+    }
+
     pub struct RedisTestController {
         pub address: String,
         server_handle: Child,
@@ -181,6 +1220,72 @@ mod cache_tests {
         }
     }
 
+    /// A TCP proxy to a Redis instance, which can reset the connection of the
+    /// client after Redis ran a command, instead of forwarding its reply.
+    pub struct RedisResetProxy {
+        pub address: String,
+        reset: Arc<AtomicBool>,
+    }
+
+    impl RedisResetProxy {
+        pub fn new(redis_address: &str) -> Result<Self> {
+            let upstream = redis_address.trim_start_matches("redis://").to_string();
+            let listener = std::net::TcpListener::bind("127.0.0.1:0")?;
+            listener.set_nonblocking(true)?;
+            let address = format!("redis://{}", listener.local_addr()?);
+            let reset = Arc::new(AtomicBool::new(false));
+
+            let armed = reset.clone();
+            let rt = tokio::runtime::Builder::new_current_thread()
+                .enable_all()
+                .build()?;
+            thread::spawn(move || {
+                rt.block_on(async move {
+                    let listener = tokio::net::TcpListener::from_std(listener).unwrap();
+                    while let Ok((client, _)) = listener.accept().await {
+                        let upstream = upstream.clone();
+                        let armed = armed.clone();
+                        tokio::spawn(async move {
+                            let upstream = tokio::net::TcpStream::connect(upstream).await?;
+                            Self::forward(client, upstream, &armed).await
+                        });
+                    }
+                })
+            });
+
+            Ok(Self { address, reset })
+        }
+
+        /// Reset the connection of the client that receives the next reply.
+        pub fn reset_next_reply(&self) {
+            self.reset.store(true, Ordering::SeqCst);
+        }
+
+        async fn forward(
+            mut client: tokio::net::TcpStream,
+            mut upstream: tokio::net::TcpStream,
+            reset: &AtomicBool,
+        ) -> io::Result<()> {
+            // Closing a connection without lingering resets it, without blocking.
+            #[allow(deprecated)]
+            client.set_linger(Some(Duration::ZERO))?;
+            let (mut request, mut reply) = ([0; 4096], [0; 4096]);
+            loop {
+                tokio::select! {
+                    n = client.read(&mut request) => match n? {
+                        0 => return Ok(()),
+                        n => upstream.write_all(&request[..n]).await?,
+                    },
+                    n = upstream.read(&mut reply) => match n? {
+                        0 => return Ok(()),
+                        _ if reset.swap(false, Ordering::SeqCst) => return Ok(()),
+                        n => client.write_all(&reply[..n]).await?,
+                    },
+                }
+            }
+        }
+    }
+
     fn get_random_port() -> u16 {
         TcpListener::bind(SocketAddrV4::new(Ipv4Addr::LOCALHOST, 0))
             .expect("Unable to bind to check for port")
@@ -190,6 +1295,158 @@ mod cache_tests {
     }
 }
 
+/// The conformance suite for the implementations of the cache interface, which
+/// checks the behavior they must share. A new implementation proves that it is
+/// compatible with the other ones by passing the suite, using `run` if it is
+/// blocking, or `run_async` if it is asynchronous.
+#[cfg(test)]
+mod cache_conformance_tests {
+    use super::{cache_tests::RedisTestController, runtime::*};
+    use anyhow::Result;
+    use cache_wasi_memory_wasmtime::MemoryCache;
+    use cache_wasi_redis_wasmtime::RedisCache;
+    use cache_wasi_sled_wasmtime::SledCache;
+    use cache_wasi_wasmtime::{asynchronous, wasi_cache, Policy, TieredCache, TieredConfig};
+    use tikv_rust_client_wasmtime::TikvClient;
+    use wasi_experimental_http_wasmtime::HttpCtx;
+    use wasmtime::Linker;
+
+    const CACHE_CONFORMANCE_TEST: &str =
+        "tests/modules/cache-conformance/target/wasm32-wasi/release/cache_conformance.wasm";
+    const CACHE_CONFORMANCE_LINKED_FS_TEST: &str =
+        "tests/modules/cache-conformance/target/wasm32-wasi/release/cache_conformance_linked_fs.wasm";
+    const CACHE_CONFORMANCE_LINKED_AZURE_TEST: &str =
+        "tests/modules/cache-conformance/target/wasm32-wasi/release/cache_conformance_linked_azure.wasm";
+
+    /// The blob endpoint of the Azurite emulator, with its well-known development account.
+    const AZURITE_BLOB_ENDPOINT: &str = "http://127.0.0.1:10000/devstoreaccount1";
+    /// The container the Azure cache tests use, which must exist in the emulator.
+    const AZURITE_CONTAINER: &str = "wasi-cache-test";
+
+    /// Run the conformance suite against a blocking host implementation.
+    pub fn run<C: wasi_cache::WasiCache + 'static>(cache: C) -> Result<()> {
+        let add_imports = |linker: &mut Linker<Context<C>>| {
+            wasi_cache::add_to_linker(linker, |ctx: &mut Context<C>| -> &mut C {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
+        exec(CACHE_CONFORMANCE_TEST, Some(cache), add_imports)
+    }
+
+    /// Run the conformance suite against an asynchronous host implementation.
+    pub async fn run_async<C>(cache: C) -> Result<()>
+    where
+        C: asynchronous::wasi_cache::WasiCache + Send + 'static,
+    {
+        let add_imports = |linker: &mut Linker<Context<C>>| {
+            asynchronous::wasi_cache::add_to_linker(linker, |ctx: &mut Context<C>| -> &mut C {
+                ctx.runtime_data.as_mut().unwrap()
+            })
+        };
+
+        exec_async(CACHE_CONFORMANCE_TEST, Some(cache), add_imports).await
+    }
+
+    #[test]
+    fn test_memory_conformance() -> Result<()> {
+        init();
+
+        run(MemoryCache::new())
+    }
+
+    #[test]
+    fn test_sled_conformance() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-sled-conformance");
+        let _ = std::fs::remove_dir_all(&path);
+        run(SledCache::open(&path)?)
+    }
+
+    #[test]
+    fn test_tiered_conformance() -> Result<()> {
+        init();
+
+        let path = std::env::temp_dir().join("wasi-cache-tiered-conformance");
+        let _ = std::fs::remove_dir_all(&path);
+        let l2 = SledCache::open(&path)?;
+        for policy in [Policy::ReadThrough, Policy::WriteThrough, Policy::WriteBack] {
+            let config = TieredConfig {
+                policy,
+                ..Default::default()
+            };
+            run(TieredCache::new(MemoryCache::new(), l2.clone(), config))?;
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_redis_conformance() -> Result<()> {
+        init();
+
+        let redis = RedisTestController::new().await?;
+        run(RedisCache::new(&redis.address)?)
+    }
+
+    #[tokio::test]
+    async fn test_redis_async_conformance() -> Result<()> {
+        init();
+
+        let redis = RedisTestController::new().await?;
+        run_async(RedisCache::builder(&redis.address).build_async().await?).await
+    }
+
+    #[test]
+    fn test_tikv_conformance() -> Result<()> {
+        init();
+
+        let client = TikvClient::builder("127.0.0.1:2379")
+            .namespace("conformance")
+            .ttl(true)
+            .build()?;
+        run(client)
+    }
+
+    #[tokio::test]
+    async fn test_tikv_async_conformance() -> Result<()> {
+        init();
+
+        let client = TikvClient::builder("127.0.0.1:2379")
+            .namespace("conformance-async")
+            .ttl(true)
+            .build_async()
+            .await?;
+        run_async(client).await
+    }
+
+    #[test]
+    fn test_fs_conformance() -> Result<()> {
+        init();
+
+        let data: Option<u32> = None;
+        exec_with_default_imports(CACHE_CONFORMANCE_LINKED_FS_TEST, data)
+    }
+
+    #[test]
+    fn test_azure_conformance() -> Result<()> {
+        init();
+
+        let env = [
+            ("STORAGE_ENDPOINT", AZURITE_BLOB_ENDPOINT),
+            ("CONTAINER", AZURITE_CONTAINER),
+        ];
+        let data: Option<u32> = None;
+        let add_imports = |linker: &mut Linker<Context<_>>| {
+            let http = HttpCtx::new(Some(vec![AZURITE_BLOB_ENDPOINT.to_string()]), None)?;
+            http.add_to_linker(linker)
+        };
+
+        exec_with_env(CACHE_CONFORMANCE_LINKED_AZURE_TEST, data, &env, add_imports)
+    }
+}
+
 #[cfg(test)]
 mod wasi_log_tests {
     use super::runtime::*;
@@ -293,6 +1550,14 @@ mod runtime {
 
     wit_bindgen_wasmtime::import!("tests/test.wit");
 
+    /// Bindings for running the test modules in engines with async support enabled.
+    mod asynchronous {
+        wit_bindgen_wasmtime::import!({
+            paths: ["tests/test.wit"],
+            async: *,
+        });
+    }
+
     pub fn init() {
         let _ = env_logger::builder().is_test(true).try_init();
     }
@@ -322,6 +1587,21 @@ mod runtime {
         exec_core(store, instance)
     }
 
+    /// Run the test module with the given environment variables set for it.
+    pub fn exec_with_env<T>(
+        wasm: &str,
+        runtime_data: Option<T>,
+        env: &[(&str, &str)],
+        add_imports: impl FnOnce(&mut Linker<Context<T>>) -> Result<()>,
+    ) -> Result<()> {
+        let mut ctx = build_ctx(runtime_data);
+        for (key, value) in env {
+            ctx.wasi.push_env(key, value)?;
+        }
+        let (store, instance) = instantiate(wasm, ctx, add_imports)?;
+        exec_core(store, instance)
+    }
+
     pub fn instantiate<T>(
         wasm: &str,
         ctx: Context<T>,
@@ -333,6 +1613,38 @@ mod runtime {
         Ok((store, instance))
     }
 
+    /// Run the test module with async support enabled in the engine, which
+    /// requires the added imports to be asynchronous.
+    pub async fn exec_async<T: Send>(
+        wasm: &str,
+        runtime_data: Option<T>,
+        add_imports: impl FnOnce(&mut Linker<Context<T>>) -> Result<()>,
+    ) -> Result<()> {
+        let mut config = default_config()?;
+        config.async_support(true);
+        let engine = Engine::new(&config)?;
+        let module = Module::from_file(&engine, wasm)?;
+        let mut linker = Linker::new(&engine);
+        wasmtime_wasi::add_to_linker(&mut linker, |cx: &mut Context<T>| &mut cx.wasi)?;
+        add_imports(&mut linker)?;
+        let mut store = Store::new(&engine, build_ctx(runtime_data));
+        let instance = linker.instantiate_async(&mut store, &module).await?;
+
+        let t = asynchronous::test::Test::new(&mut store, &instance, |host| {
+            host.async_test_data.as_mut().unwrap()
+        })?;
+        let result = t
+            .test(&mut store)
+            .await
+            .expect("Error running the test method");
+        match result {
+            Ok(()) | Err(asynchronous::test::Error::Success) => Ok(()),
+            Err(asynchronous::test::Error::Failure) => {
+                Err(anyhow::anyhow!("Test returned failure"))
+            }
+        }
+    }
+
     fn emls<T>(
         wasm: &str,
         ctx: Context<T>,
@@ -380,15 +1692,18 @@ mod runtime {
         pub wasi: WasiCtx,
         pub runtime_data: Option<T>,
         pub test_data: Option<test::TestData>,
+        pub async_test_data: Option<asynchronous::test::TestData>,
     }
 
     fn build_ctx<T>(runtime_data: Option<T>) -> Context<T> {
         let wasi = default_wasi();
         let test_data = Some(test::TestData::default());
+        let async_test_data = Some(asynchronous::test::TestData::default());
         Context {
             wasi,
             runtime_data,
             test_data,
+            async_test_data,
         }
     }
 }
@@ -0,0 +1,367 @@
+//! Conformance suite for the implementations of the cache interface.
+//! It checks the behavior every implementation must share, so that any
+//! implementation can be tested by running this module with it. Implementations
+//! that do not support the atomic operations must fail them with an `other`
+//! error, and the checks of those operations are then skipped.
+
+use std::{collections::BTreeSet, thread, time::Duration};
+
+wit_bindgen_rust::import!("../../../wit/ephemeral/wasi-cache.wit");
+wit_bindgen_rust::export!("../../test.wit");
+
+/// The prefix of every key used by the suite.
+const PREFIX: &str = "conformance-";
+
+/// The size of the large payloads, above the size of a single Azure block.
+const LARGE_VALUE_SIZE: usize = 5 * 1024 * 1024;
+
+struct Test {}
+
+impl test::Test for Test {
+    fn test() -> Result<(), test::Error> {
+        set_get_delete()?;
+        missing_keys()?;
+        batches()?;
+        ttl_expiry()?;
+        large_values()?;
+        binary_data()?;
+        unusual_keys()?;
+        increment()?;
+        compare_and_swap()?;
+        list_keys()?;
+        Ok(())
+    }
+}
+
+/// Payloads can be written, overwritten, read, and deleted.
+fn set_get_delete() -> Result<(), wasi_cache::Error> {
+    let key = &key("the_one_ring");
+    println!(
+        "cache_conformance:: writing, reading, and deleting key {}",
+        key
+    );
+
+    wasi_cache::set(key, b"Precious", None)?;
+    assert_eq!(Some(b"Precious".to_vec()), wasi_cache::get(key)?);
+
+    wasi_cache::set(key, b"Isildur's Bane", None)?;
+    assert_eq!(Some(b"Isildur's Bane".to_vec()), wasi_cache::get(key)?);
+
+    wasi_cache::set(key, b"", None)?;
+    assert_eq!(Some(Vec::new()), wasi_cache::get(key)?);
+
+    wasi_cache::delete(key)?;
+    assert_eq!(None, wasi_cache::get(key)?);
+    Ok(())
+}
+
+/// Reading a missing key is a miss, and deleting it succeeds.
+fn missing_keys() -> Result<(), wasi_cache::Error> {
+    let lost = &key("the_lost_ring");
+    println!(
+        "cache_conformance:: reading and deleting missing key {}",
+        lost
+    );
+
+    wasi_cache::delete(lost)?;
+    assert_eq!(None, wasi_cache::get(lost)?);
+    wasi_cache::delete(lost)?;
+
+    let keys = [key("nenya"), key("vilya"), key("narya")];
+    let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    println!(
+        "cache_conformance:: reading and deleting keys {:?} with missing ones",
+        keys
+    );
+
+    wasi_cache::delete_many(&keys)?;
+    wasi_cache::set(keys[1], b"Ring of Air", None)?;
+    assert_eq!(
+        vec![None, Some(b"Ring of Air".to_vec()), None],
+        wasi_cache::get_many(&keys)?
+    );
+
+    wasi_cache::delete_many(&keys)?;
+    assert_eq!(vec![None, None, None], wasi_cache::get_many(&keys)?);
+    Ok(())
+}
+
+/// Batches of entries can be written, overwritten, read in any order, and deleted.
+fn batches() -> Result<(), wasi_cache::Error> {
+    let keys = [key("gimli"), key("legolas"), key("aragorn")];
+    let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    println!("cache_conformance:: writing and reading keys {:?}", keys);
+
+    let entries: &[(&str, &[u8])] = &[(keys[0], b"Axe"), (keys[1], b"Bow"), (keys[2], b"")];
+    wasi_cache::set_many(entries, None)?;
+    assert_eq!(
+        vec![
+            Some(Vec::new()),
+            Some(b"Axe".to_vec()),
+            Some(b"Bow".to_vec())
+        ],
+        wasi_cache::get_many(&[keys[2], keys[0], keys[1]])?
+    );
+
+    let entries: &[(&str, &[u8])] = &[(keys[2], b"Anduril")];
+    wasi_cache::set_many(entries, None)?;
+    assert_eq!(Some(b"Anduril".to_vec()), wasi_cache::get(keys[2])?);
+
+    wasi_cache::delete_many(&keys[..2])?;
+    assert_eq!(
+        vec![None, None, Some(b"Anduril".to_vec())],
+        wasi_cache::get_many(&keys)?
+    );
+    wasi_cache::delete_many(&keys)?;
+    Ok(())
+}
+
+/// Entries set with a time-to-live are missing once it elapsed, and
+/// entries set without one are not. Their remaining lifetime is reported,
+/// and entries set with a zero time-to-live expire immediately.
+fn ttl_expiry() -> Result<(), wasi_cache::Error> {
+    let (expiring, persistent) = (&key("gandalf_the_grey"), &key("gandalf_the_white"));
+    println!(
+        "cache_conformance:: reading keys {} and {} after expiration",
+        expiring, persistent
+    );
+
+    wasi_cache::set(expiring, b"Mithrandir", Some(1))?;
+    wasi_cache::set(persistent, b"Mithrandir", None)?;
+    assert_eq!(Some(b"Mithrandir".to_vec()), wasi_cache::get(expiring)?);
+    assert!(matches!(
+        wasi_cache::get_ttl(expiring)?,
+        Some(wasi_cache::Lifetime::Remaining(s)) if s <= 1
+    ));
+    assert!(matches!(
+        wasi_cache::get_ttl(persistent)?,
+        Some(wasi_cache::Lifetime::Persistent)
+    ));
+
+    let vanished = &key("olorin");
+    println!(
+        "cache_conformance:: writing key {} with no time-to-live left",
+        vanished
+    );
+    wasi_cache::set(vanished, b"Mithrandir", None)?;
+    wasi_cache::set(vanished, b"Mithrandir", Some(0))?;
+    assert_eq!(None, wasi_cache::get(vanished)?);
+    let entries: &[(&str, &[u8])] = &[(vanished, b"Mithrandir")];
+    wasi_cache::set_many(entries, Some(0))?;
+    assert_eq!(None, wasi_cache::get(vanished)?);
+
+    // Expiration times may be rounded to the second.
+    thread::sleep(Duration::from_millis(2500));
+    assert_eq!(None, wasi_cache::get(expiring)?);
+    assert!(wasi_cache::get_ttl(expiring)?.is_none());
+    assert_eq!(Some(b"Mithrandir".to_vec()), wasi_cache::get(persistent)?);
+
+    wasi_cache::delete_many(&[expiring.as_str(), persistent.as_str()])?;
+    Ok(())
+}
+
+/// Large payloads are stored in full.
+fn large_values() -> Result<(), wasi_cache::Error> {
+    let key = &key("the_red_book_of_westmarch");
+    println!(
+        "cache_conformance:: writing {} bytes to key {}",
+        LARGE_VALUE_SIZE, key
+    );
+
+    let value = (0..LARGE_VALUE_SIZE)
+        .map(|i| (i % 251) as u8)
+        .collect::<Vec<_>>();
+    wasi_cache::set(key, &value, None)?;
+    assert!(wasi_cache::get(key)? == Some(value));
+
+    wasi_cache::delete(key)?;
+    Ok(())
+}
+
+/// Payloads are arbitrary bytes, which are not interpreted.
+fn binary_data() -> Result<(), wasi_cache::Error> {
+    let key = &key("moria_gate");
+    println!("cache_conformance:: writing binary data to key {}", key);
+
+    let values: [&[u8]; 4] = [
+        &[0, 1, 2, 0, 255],
+        &[0xff, 0xfe, 0xfd],
+        &[0xc3, 0x28],
+        b"\r\n\0speak friend\0\r\n",
+    ];
+    for value in values {
+        wasi_cache::set(key, value, None)?;
+        assert_eq!(Some(value.to_vec()), wasi_cache::get(key)?);
+    }
+
+    wasi_cache::delete(key)?;
+    Ok(())
+}
+
+/// Keys are arbitrary strings, which do not collide with each other.
+fn unusual_keys() -> Result<(), wasi_cache::Error> {
+    let long = "e".repeat(300);
+    let keys = [
+        key("key with spaces"),
+        key("Mixed-Case"),
+        key("mixed-case"),
+        key("path/to/key"),
+        key("namespace:key"),
+        key("dots.and_underscores"),
+        key("ключ-🔑"),
+        key(&long),
+    ];
+    println!("cache_conformance:: writing {} unusual keys", keys.len());
+
+    for key in &keys {
+        wasi_cache::set(key, key.as_bytes(), None)?;
+    }
+    for key in &keys {
+        assert_eq!(Some(key.as_bytes().to_vec()), wasi_cache::get(key)?);
+    }
+
+    for key in &keys {
+        wasi_cache::delete(key)?;
+        assert_eq!(None, wasi_cache::get(key)?);
+    }
+    Ok(())
+}
+
+/// Integers are incremented atomically, starting from zero for missing keys,
+/// and stored as their decimal representation.
+fn increment() -> Result<(), wasi_cache::Error> {
+    let key = &key("fellowship_members");
+    println!("cache_conformance:: incrementing key {}", key);
+
+    wasi_cache::delete(key)?;
+    let members = match wasi_cache::increment(key, 9) {
+        Err(wasi_cache::Error::Other(msg)) => {
+            println!(
+                "cache_conformance:: skipping unsupported increment: {}",
+                msg
+            );
+            return Ok(());
+        }
+        res => res?,
+    };
+    assert_eq!(9, members);
+    assert_eq!(Some(b"9".to_vec()), wasi_cache::get(key)?);
+    assert_eq!(7, wasi_cache::increment(key, -2)?);
+    assert_eq!(-3, wasi_cache::increment(key, -10)?);
+
+    wasi_cache::set(key, b"41", None)?;
+    assert_eq!(42, wasi_cache::increment(key, 1)?);
+
+    // Payloads that are not integers cannot be incremented.
+    wasi_cache::set(key, b"nine", None)?;
+    assert!(wasi_cache::increment(key, 1).is_err());
+    assert_eq!(Some(b"nine".to_vec()), wasi_cache::get(key)?);
+
+    wasi_cache::delete(key)?;
+    Ok(())
+}
+
+/// Payloads are only replaced if they are equal to the expected ones, and
+/// an empty payload is distinct from a missing one.
+fn compare_and_swap() -> Result<(), wasi_cache::Error> {
+    let key = &key("the_ring_bearer");
+    println!("cache_conformance:: compare and swap key {}", key);
+
+    wasi_cache::delete(key)?;
+    let swapped = match wasi_cache::compare_and_swap(key, Some(b"Isildur"), b"Gollum") {
+        Err(wasi_cache::Error::Other(msg)) => {
+            println!(
+                "cache_conformance:: skipping unsupported compare-and-swap: {}",
+                msg
+            );
+            return Ok(());
+        }
+        res => res?,
+    };
+    assert!(!swapped);
+    assert_eq!(None, wasi_cache::get(key)?);
+
+    assert!(wasi_cache::compare_and_swap(key, None, b"Isildur")?);
+    assert!(!wasi_cache::compare_and_swap(key, None, b"Gollum")?);
+    assert!(!wasi_cache::compare_and_swap(
+        key,
+        Some(b"Gollum"),
+        b"Bilbo"
+    )?);
+    assert_eq!(Some(b"Isildur".to_vec()), wasi_cache::get(key)?);
+    assert!(wasi_cache::compare_and_swap(
+        key,
+        Some(b"Isildur"),
+        b"Gollum"
+    )?);
+    assert_eq!(Some(b"Gollum".to_vec()), wasi_cache::get(key)?);
+
+    wasi_cache::set(key, b"", None)?;
+    assert!(!wasi_cache::compare_and_swap(key, None, b"Bilbo")?);
+    assert!(wasi_cache::compare_and_swap(key, Some(b""), b"Bilbo")?);
+    assert_eq!(Some(b"Bilbo".to_vec()), wasi_cache::get(key)?);
+
+    wasi_cache::delete(key)?;
+    Ok(())
+}
+
+/// Listing the keys with a prefix returns every live key with that prefix,
+/// across pages. As with Redis, a key may be returned in more than one page.
+fn list_keys() -> Result<(), wasi_cache::Error> {
+    let prefix = &key("hobbit-");
+    let names = ["bilbo", "frodo", "merry", "pippin", "sam"];
+    let keys = names
+        .iter()
+        .map(|n| format!("{}{}", prefix, n))
+        .collect::<Vec<_>>();
+    let others = [key("hobbit"), key("hobbits-of-bree"), key("dwarf-gimli")];
+    println!(
+        "cache_conformance:: listing {} keys with prefix {}",
+        keys.len(),
+        prefix
+    );
+
+    for key in keys.iter().chain(&others) {
+        wasi_cache::set(key, b"Shire", None)?;
+    }
+    // Expired keys are not listed.
+    let expired = &format!("{}gollum", prefix);
+    wasi_cache::set(expired, b"Shire", Some(0))?;
+    assert_eq!(keys.iter().cloned().collect::<BTreeSet<_>>(), list(prefix)?);
+
+    let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+    wasi_cache::delete_many(&keys)?;
+    assert!(list(prefix)?.is_empty());
+
+    wasi_cache::delete(expired)?;
+    for key in &others {
+        wasi_cache::delete(key)?;
+    }
+    Ok(())
+}
+
+/// Return every key with the given prefix, reading pages of two keys.
+fn list(prefix: &str) -> Result<BTreeSet<String>, wasi_cache::Error> {
+    let mut keys = BTreeSet::new();
+    let mut cursor = None;
+    loop {
+        let page = wasi_cache::list_keys(prefix, cursor.as_deref(), 2)?;
+        keys.extend(page.keys);
+        match page.cursor {
+            Some(c) => cursor = Some(c),
+            None => return Ok(keys),
+        }
+    }
+}
+
+/// Return the key used by the suite for the given name.
+fn key(name: &str) -> String {
+    format!("{}{}", PREFIX, name)
+}
+
+impl From<wasi_cache::Error> for test::Error {
+    fn from(e: wasi_cache::Error) -> Self {
+        println!("cache_conformance:: unexpected error {:?}", e);
+        Self::Failure
+    }
+}
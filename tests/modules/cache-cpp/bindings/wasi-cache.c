@@ -43,25 +43,125 @@ void wasi_cache_string_free(wasi_cache_string_t *ret) {
 void wasi_cache_payload_free(wasi_cache_payload_t *ptr) {
   canonical_abi_free(ptr->ptr, ptr->len * 1, 1);
 }
-typedef struct {
-  // 0 if `val` is `ok`, 1 otherwise
-  uint8_t tag;
-  union {
-    wasi_cache_error_t err;
-  } val;
-} wasi_cache_expected_void_error_t;
-typedef struct {
-  // 0 if `val` is `ok`, 1 otherwise
-  uint8_t tag;
-  union {
-    wasi_cache_payload_t ok;
-    wasi_cache_error_t err;
-  } val;
-} wasi_cache_expected_payload_error_t;
-static int64_t RET_AREA[3];
+void wasi_cache_error_free(wasi_cache_error_t *ptr) {
+  switch ((int32_t) ptr->tag) {
+    case 0: {
+      wasi_cache_string_free(&ptr->val.not_found);
+      break;
+    }
+    case 1: {
+      wasi_cache_string_free(&ptr->val.invalid_key);
+      break;
+    }
+    case 2: {
+      wasi_cache_string_free(&ptr->val.value_too_large);
+      break;
+    }
+    case 3: {
+      wasi_cache_string_free(&ptr->val.backend_unavailable);
+      break;
+    }
+    case 4: {
+      wasi_cache_string_free(&ptr->val.timeout);
+      break;
+    }
+    case 5: {
+      wasi_cache_string_free(&ptr->val.permission_denied);
+      break;
+    }
+    case 6: {
+      wasi_cache_string_free(&ptr->val.other);
+      break;
+    }
+  }
+}
+void wasi_cache_list_string_free(wasi_cache_list_string_t *ptr) {
+  for (size_t i = 0; i < ptr->len; i++) {
+    wasi_cache_string_free(&ptr->ptr[i]);
+  }
+  canonical_abi_free(ptr->ptr, ptr->len * 8, 4);
+}
+void wasi_cache_option_string_free(wasi_cache_option_string_t *ptr) {
+  switch ((int32_t) ptr->tag) {
+    case 1: {
+      wasi_cache_string_free(&ptr->val);
+      break;
+    }
+  }
+}
+void wasi_cache_key_page_free(wasi_cache_key_page_t *ptr) {
+  wasi_cache_list_string_free(&ptr->keys);
+  wasi_cache_option_string_free(&ptr->cursor);
+}
+void wasi_cache_expected_void_error_free(wasi_cache_expected_void_error_t *ptr) {
+  if (ptr->tag) {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_option_payload_free(wasi_cache_option_payload_t *ptr) {
+  switch ((int32_t) ptr->tag) {
+    case 1: {
+      wasi_cache_payload_free(&ptr->val);
+      break;
+    }
+  }
+}
+void wasi_cache_expected_option_payload_error_free(wasi_cache_expected_option_payload_error_t *ptr) {
+  if (!ptr->tag) {
+    wasi_cache_option_payload_free(&ptr->val.ok);
+  } else {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_list_option_payload_free(wasi_cache_list_option_payload_t *ptr) {
+  for (size_t i = 0; i < ptr->len; i++) {
+    wasi_cache_option_payload_free(&ptr->ptr[i]);
+  }
+  canonical_abi_free(ptr->ptr, ptr->len * 12, 4);
+}
+void wasi_cache_expected_list_option_payload_error_free(wasi_cache_expected_list_option_payload_error_t *ptr) {
+  if (!ptr->tag) {
+    wasi_cache_list_option_payload_free(&ptr->val.ok);
+  } else {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_tuple2_string_payload_free(wasi_cache_tuple2_string_payload_t *ptr) {
+  wasi_cache_string_free(&ptr->f0);
+  wasi_cache_payload_free(&ptr->f1);
+}
+void wasi_cache_list_tuple2_string_payload_free(wasi_cache_list_tuple2_string_payload_t *ptr) {
+  for (size_t i = 0; i < ptr->len; i++) {
+    wasi_cache_tuple2_string_payload_free(&ptr->ptr[i]);
+  }
+  canonical_abi_free(ptr->ptr, ptr->len * 16, 4);
+}
+void wasi_cache_expected_s64_error_free(wasi_cache_expected_s64_error_t *ptr) {
+  if (ptr->tag) {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_expected_bool_error_free(wasi_cache_expected_bool_error_t *ptr) {
+  if (ptr->tag) {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_expected_key_page_error_free(wasi_cache_expected_key_page_error_t *ptr) {
+  if (!ptr->tag) {
+    wasi_cache_key_page_free(&ptr->val.ok);
+  } else {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+void wasi_cache_expected_option_lifetime_error_free(wasi_cache_expected_option_lifetime_error_t *ptr) {
+  if (ptr->tag) {
+    wasi_cache_error_free(&ptr->val.err);
+  }
+}
+static int64_t RET_AREA[6];
 __attribute__((import_module("wasi-cache"), import_name("set")))
 void __wasm_import_wasi_cache_set(int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t);
-wasi_cache_error_t wasi_cache_set(wasi_cache_string_t *key, wasi_cache_payload_t *value, wasi_cache_option_u32_t *ttl) {
+void wasi_cache_set(wasi_cache_string_t *key, wasi_cache_payload_t *value, wasi_cache_option_u32_t *ttl, wasi_cache_expected_void_error_t *ret0) {
   int32_t variant;
   int32_t variant1;
   switch ((int32_t) (*ttl).tag) {
@@ -79,54 +179,599 @@ wasi_cache_error_t wasi_cache_set(wasi_cache_string_t *key, wasi_cache_payload_t
   }
   int32_t ptr = (int32_t) &RET_AREA;
   __wasm_import_wasi_cache_set((int32_t) (*key).ptr, (int32_t) (*key).len, (int32_t) (*value).ptr, (int32_t) (*value).len, variant, variant1, ptr);
-  wasi_cache_expected_void_error_t variant2;
-  variant2.tag = *((int32_t*) (ptr + 0));
-  switch ((int32_t) variant2.tag) {
+  wasi_cache_expected_void_error_t variant3;
+  variant3.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant3.tag) {
     case 0: {
       break;
     }
     case 1: {
-      variant2.val.err = *((int32_t*) (ptr + 8));
+      wasi_cache_error_t variant2;
+      variant2.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant2.tag) {
+        case 0: {
+          variant2.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant2.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant2.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant2.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant2.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant2.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant2.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant3.val.err = variant2;
       break;
     }
   }
-  return variant2.tag ? variant2.val.err : -1;
+  *ret0 = variant3;
 }
 __attribute__((import_module("wasi-cache"), import_name("get")))
 void __wasm_import_wasi_cache_get(int32_t, int32_t, int32_t);
-wasi_cache_error_t wasi_cache_get(wasi_cache_string_t *key, wasi_cache_payload_t *ret0) {
+void wasi_cache_get(wasi_cache_string_t *key, wasi_cache_expected_option_payload_error_t *ret0) {
   int32_t ptr = (int32_t) &RET_AREA;
   __wasm_import_wasi_cache_get((int32_t) (*key).ptr, (int32_t) (*key).len, ptr);
-  wasi_cache_expected_payload_error_t variant;
-  variant.tag = *((int32_t*) (ptr + 0));
-  switch ((int32_t) variant.tag) {
+  wasi_cache_expected_option_payload_error_t variant2;
+  variant2.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant2.tag) {
     case 0: {
-      variant.val.ok = (wasi_cache_payload_t) { (uint8_t*)(*((int32_t*) (ptr + 8))), (size_t)(*((int32_t*) (ptr + 16))) };
+      wasi_cache_option_payload_t variant;
+      variant.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant.tag) {
+        case 0: {
+          break;
+        }
+        case 1: {
+          variant.val = (wasi_cache_payload_t) { (uint8_t*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant2.val.ok = variant;
       break;
     }
     case 1: {
-      variant.val.err = *((int32_t*) (ptr + 8));
+      wasi_cache_error_t variant1;
+      variant1.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant1.tag) {
+        case 0: {
+          variant1.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant1.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant1.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant1.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant1.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant1.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant1.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant2.val.err = variant1;
       break;
     }
   }
-  *ret0 = variant.val.ok;
-  return variant.tag ? variant.val.err : -1;
+  *ret0 = variant2;
 }
 __attribute__((import_module("wasi-cache"), import_name("delete")))
 void __wasm_import_wasi_cache_delete(int32_t, int32_t, int32_t);
-wasi_cache_error_t wasi_cache_delete(wasi_cache_string_t *key) {
+void wasi_cache_delete(wasi_cache_string_t *key, wasi_cache_expected_void_error_t *ret0) {
   int32_t ptr = (int32_t) &RET_AREA;
   __wasm_import_wasi_cache_delete((int32_t) (*key).ptr, (int32_t) (*key).len, ptr);
-  wasi_cache_expected_void_error_t variant;
-  variant.tag = *((int32_t*) (ptr + 0));
-  switch ((int32_t) variant.tag) {
+  wasi_cache_expected_void_error_t variant1;
+  variant1.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant1.tag) {
+    case 0: {
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant;
+      variant.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant.tag) {
+        case 0: {
+          variant.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant1.val.err = variant;
+      break;
+    }
+  }
+  *ret0 = variant1;
+}
+__attribute__((import_module("wasi-cache"), import_name("get-many")))
+void __wasm_import_wasi_cache_get_many(int32_t, int32_t, int32_t);
+void wasi_cache_get_many(wasi_cache_list_string_t *keys, wasi_cache_expected_list_option_payload_error_t *ret0) {
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_get_many((int32_t) (*keys).ptr, (int32_t) (*keys).len, ptr);
+  wasi_cache_expected_list_option_payload_error_t variant1;
+  variant1.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant1.tag) {
+    case 0: {
+      variant1.val.ok = (wasi_cache_list_option_payload_t) { (wasi_cache_option_payload_t*)(*((int32_t*) (ptr + 8))), (size_t)(*((int32_t*) (ptr + 16))) };
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant;
+      variant.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant.tag) {
+        case 0: {
+          variant.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant1.val.err = variant;
+      break;
+    }
+  }
+  *ret0 = variant1;
+}
+__attribute__((import_module("wasi-cache"), import_name("set-many")))
+void __wasm_import_wasi_cache_set_many(int32_t, int32_t, int32_t, int32_t, int32_t);
+void wasi_cache_set_many(wasi_cache_list_tuple2_string_payload_t *entries, wasi_cache_option_u32_t *ttl, wasi_cache_expected_void_error_t *ret0) {
+  int32_t variant;
+  int32_t variant1;
+  switch ((int32_t) (*ttl).tag) {
+    case 0: {
+      variant = 0;
+      variant1 = 0;
+      break;
+    }
+    case 1: {
+      const uint32_t *payload0 = &(*ttl).val;
+      variant = 1;
+      variant1 = (int32_t) (*payload0);
+      break;
+    }
+  }
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_set_many((int32_t) (*entries).ptr, (int32_t) (*entries).len, variant, variant1, ptr);
+  wasi_cache_expected_void_error_t variant3;
+  variant3.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant3.tag) {
+    case 0: {
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant2;
+      variant2.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant2.tag) {
+        case 0: {
+          variant2.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant2.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant2.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant2.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant2.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant2.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant2.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant3.val.err = variant2;
+      break;
+    }
+  }
+  *ret0 = variant3;
+}
+__attribute__((import_module("wasi-cache"), import_name("delete-many")))
+void __wasm_import_wasi_cache_delete_many(int32_t, int32_t, int32_t);
+void wasi_cache_delete_many(wasi_cache_list_string_t *keys, wasi_cache_expected_void_error_t *ret0) {
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_delete_many((int32_t) (*keys).ptr, (int32_t) (*keys).len, ptr);
+  wasi_cache_expected_void_error_t variant1;
+  variant1.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant1.tag) {
+    case 0: {
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant;
+      variant.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant.tag) {
+        case 0: {
+          variant.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant1.val.err = variant;
+      break;
+    }
+  }
+  *ret0 = variant1;
+}
+__attribute__((import_module("wasi-cache"), import_name("increment")))
+void __wasm_import_wasi_cache_increment(int32_t, int32_t, int64_t, int32_t);
+void wasi_cache_increment(wasi_cache_string_t *key, int64_t delta, wasi_cache_expected_s64_error_t *ret0) {
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_increment((int32_t) (*key).ptr, (int32_t) (*key).len, delta, ptr);
+  wasi_cache_expected_s64_error_t variant1;
+  variant1.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant1.tag) {
+    case 0: {
+      variant1.val.ok = *((int64_t*) (ptr + 8));
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant;
+      variant.tag = (int32_t) (*((int64_t*) (ptr + 8)));
+      switch ((int32_t) variant.tag) {
+        case 0: {
+          variant.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant1.val.err = variant;
+      break;
+    }
+  }
+  *ret0 = variant1;
+}
+__attribute__((import_module("wasi-cache"), import_name("compare-and-swap")))
+void __wasm_import_wasi_cache_compare_and_swap(int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t);
+void wasi_cache_compare_and_swap(wasi_cache_string_t *key, wasi_cache_option_payload_t *old, wasi_cache_payload_t *value, wasi_cache_expected_bool_error_t *ret0) {
+  int32_t variant;
+  int32_t variant1;
+  int32_t variant2;
+  switch ((int32_t) (*old).tag) {
+    case 0: {
+      variant = 0;
+      variant1 = 0;
+      variant2 = 0;
+      break;
+    }
+    case 1: {
+      const wasi_cache_payload_t *payload0 = &(*old).val;
+      variant = 1;
+      variant1 = (int32_t) (*payload0).ptr;
+      variant2 = (int32_t) (*payload0).len;
+      break;
+    }
+  }
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_compare_and_swap((int32_t) (*key).ptr, (int32_t) (*key).len, variant, variant1, variant2, (int32_t) (*value).ptr, (int32_t) (*value).len, ptr);
+  wasi_cache_expected_bool_error_t variant4;
+  variant4.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant4.tag) {
+    case 0: {
+      variant4.val.ok = *((int32_t*) (ptr + 8));
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant3;
+      variant3.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant3.tag) {
+        case 0: {
+          variant3.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant3.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant3.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant3.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant3.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant3.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant3.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant4.val.err = variant3;
+      break;
+    }
+  }
+  *ret0 = variant4;
+}
+__attribute__((import_module("wasi-cache"), import_name("list-keys")))
+void __wasm_import_wasi_cache_list_keys(int32_t, int32_t, int32_t, int32_t, int32_t, int32_t, int32_t);
+void wasi_cache_list_keys(wasi_cache_string_t *prefix, wasi_cache_option_string_t *cursor, uint32_t limit, wasi_cache_expected_key_page_error_t *ret0) {
+  int32_t variant;
+  int32_t variant1;
+  int32_t variant2;
+  switch ((int32_t) (*cursor).tag) {
+    case 0: {
+      variant = 0;
+      variant1 = 0;
+      variant2 = 0;
+      break;
+    }
+    case 1: {
+      const wasi_cache_string_t *payload0 = &(*cursor).val;
+      variant = 1;
+      variant1 = (int32_t) (*payload0).ptr;
+      variant2 = (int32_t) (*payload0).len;
+      break;
+    }
+  }
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_list_keys((int32_t) (*prefix).ptr, (int32_t) (*prefix).len, variant, variant1, variant2, (int32_t) (limit), ptr);
+  wasi_cache_expected_key_page_error_t variant5;
+  variant5.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant5.tag) {
+    case 0: {
+      wasi_cache_option_string_t variant3;
+      variant3.tag = *((int32_t*) (ptr + 24));
+      switch ((int32_t) variant3.tag) {
+        case 0: {
+          break;
+        }
+        case 1: {
+          variant3.val = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 32))), (size_t)(*((int32_t*) (ptr + 40))) };
+          break;
+        }
+      }
+      variant5.val.ok = (wasi_cache_key_page_t) { (wasi_cache_list_string_t) { (wasi_cache_string_t*)(*((int32_t*) (ptr + 8))), (size_t)(*((int32_t*) (ptr + 16))) }, variant3 };
+      break;
+    }
+    case 1: {
+      wasi_cache_error_t variant4;
+      variant4.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant4.tag) {
+        case 0: {
+          variant4.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant4.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant4.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant4.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant4.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant4.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant4.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant5.val.err = variant4;
+      break;
+    }
+  }
+  *ret0 = variant5;
+}
+__attribute__((import_module("wasi-cache"), import_name("get-ttl")))
+void __wasm_import_wasi_cache_get_ttl(int32_t, int32_t, int32_t);
+void wasi_cache_get_ttl(wasi_cache_string_t *key, wasi_cache_expected_option_lifetime_error_t *ret0) {
+  int32_t ptr = (int32_t) &RET_AREA;
+  __wasm_import_wasi_cache_get_ttl((int32_t) (*key).ptr, (int32_t) (*key).len, ptr);
+  wasi_cache_expected_option_lifetime_error_t variant4;
+  variant4.tag = *((int32_t*) (ptr + 0));
+  switch ((int32_t) variant4.tag) {
     case 0: {
+      wasi_cache_option_lifetime_t variant2;
+      variant2.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant2.tag) {
+        case 0: {
+          break;
+        }
+        case 1: {
+          wasi_cache_lifetime_t variant;
+          variant.tag = *((int32_t*) (ptr + 16));
+          switch ((int32_t) variant.tag) {
+            case 0: {
+              break;
+            }
+            case 1: {
+              variant.val.remaining = (uint32_t) (*((int32_t*) (ptr + 24)));
+              break;
+            }
+          }
+          variant2.val = variant;
+          break;
+        }
+      }
+      variant4.val.ok = variant2;
       break;
     }
     case 1: {
-      variant.val.err = *((int32_t*) (ptr + 8));
+      wasi_cache_error_t variant3;
+      variant3.tag = *((int32_t*) (ptr + 8));
+      switch ((int32_t) variant3.tag) {
+        case 0: {
+          variant3.val.not_found = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 1: {
+          variant3.val.invalid_key = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 2: {
+          variant3.val.value_too_large = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 3: {
+          variant3.val.backend_unavailable = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 4: {
+          variant3.val.timeout = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 5: {
+          variant3.val.permission_denied = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+        case 6: {
+          variant3.val.other = (wasi_cache_string_t) { (char*)(*((int32_t*) (ptr + 16))), (size_t)(*((int32_t*) (ptr + 24))) };
+          break;
+        }
+      }
+      variant4.val.err = variant3;
       break;
     }
   }
-  return variant.tag ? variant.val.err : -1;
+  *ret0 = variant4;
 }
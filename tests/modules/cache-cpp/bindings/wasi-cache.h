@@ -16,23 +16,162 @@ extern "C"
   void wasi_cache_string_set(wasi_cache_string_t *ret, const char *s);
   void wasi_cache_string_dup(wasi_cache_string_t *ret, const char *s);
   void wasi_cache_string_free(wasi_cache_string_t *ret);
-  // General purpose error.
-  typedef uint8_t wasi_cache_error_t;
-  #define WASI_CACHE_ERROR_SUCCESS 0
-  #define WASI_CACHE_ERROR_ERROR 1
   typedef struct {
     uint8_t *ptr;
     size_t len;
   } wasi_cache_payload_t;
   void wasi_cache_payload_free(wasi_cache_payload_t *ptr);
+  // Errors returned by the cache.
+  // Every case contains a message describing the error.
+  typedef struct {
+    uint8_t tag;
+    union {
+      wasi_cache_string_t not_found;
+      wasi_cache_string_t invalid_key;
+      wasi_cache_string_t value_too_large;
+      wasi_cache_string_t backend_unavailable;
+      wasi_cache_string_t timeout;
+      wasi_cache_string_t permission_denied;
+      wasi_cache_string_t other;
+    } val;
+  } wasi_cache_error_t;
+  #define WASI_CACHE_ERROR_NOT_FOUND 0
+  #define WASI_CACHE_ERROR_INVALID_KEY 1
+  #define WASI_CACHE_ERROR_VALUE_TOO_LARGE 2
+  #define WASI_CACHE_ERROR_BACKEND_UNAVAILABLE 3
+  #define WASI_CACHE_ERROR_TIMEOUT 4
+  #define WASI_CACHE_ERROR_PERMISSION_DENIED 5
+  #define WASI_CACHE_ERROR_OTHER 6
+  void wasi_cache_error_free(wasi_cache_error_t *ptr);
+  typedef struct {
+    wasi_cache_string_t *ptr;
+    size_t len;
+  } wasi_cache_list_string_t;
+  void wasi_cache_list_string_free(wasi_cache_list_string_t *ptr);
+  typedef struct {
+    // `true` if `val` is present, `false` otherwise
+    bool tag;
+    wasi_cache_string_t val;
+  } wasi_cache_option_string_t;
+  void wasi_cache_option_string_free(wasi_cache_option_string_t *ptr);
+  // A page of keys returned when listing the cache.
+  typedef struct {
+    wasi_cache_list_string_t keys;
+    wasi_cache_option_string_t cursor;
+  } wasi_cache_key_page_t;
+  void wasi_cache_key_page_free(wasi_cache_key_page_t *ptr);
+  // The remaining lifetime of a cache entry.
+  typedef struct {
+    uint8_t tag;
+    union {
+      uint32_t remaining;
+    } val;
+  } wasi_cache_lifetime_t;
+  #define WASI_CACHE_LIFETIME_PERSISTENT 0
+  #define WASI_CACHE_LIFETIME_REMAINING 1
   typedef struct {
     // `true` if `val` is present, `false` otherwise
     bool tag;
     uint32_t val;
   } wasi_cache_option_u32_t;
-  wasi_cache_error_t wasi_cache_set(wasi_cache_string_t *key, wasi_cache_payload_t *value, wasi_cache_option_u32_t *ttl);
-  wasi_cache_error_t wasi_cache_get(wasi_cache_string_t *key, wasi_cache_payload_t *ret0);
-  wasi_cache_error_t wasi_cache_delete(wasi_cache_string_t *key);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_void_error_t;
+  void wasi_cache_expected_void_error_free(wasi_cache_expected_void_error_t *ptr);
+  typedef struct {
+    // `true` if `val` is present, `false` otherwise
+    bool tag;
+    wasi_cache_payload_t val;
+  } wasi_cache_option_payload_t;
+  void wasi_cache_option_payload_free(wasi_cache_option_payload_t *ptr);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      wasi_cache_option_payload_t ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_option_payload_error_t;
+  void wasi_cache_expected_option_payload_error_free(wasi_cache_expected_option_payload_error_t *ptr);
+  typedef struct {
+    wasi_cache_option_payload_t *ptr;
+    size_t len;
+  } wasi_cache_list_option_payload_t;
+  void wasi_cache_list_option_payload_free(wasi_cache_list_option_payload_t *ptr);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      wasi_cache_list_option_payload_t ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_list_option_payload_error_t;
+  void wasi_cache_expected_list_option_payload_error_free(wasi_cache_expected_list_option_payload_error_t *ptr);
+  typedef struct {
+    wasi_cache_string_t f0;
+    wasi_cache_payload_t f1;
+  } wasi_cache_tuple2_string_payload_t;
+  void wasi_cache_tuple2_string_payload_free(wasi_cache_tuple2_string_payload_t *ptr);
+  typedef struct {
+    wasi_cache_tuple2_string_payload_t *ptr;
+    size_t len;
+  } wasi_cache_list_tuple2_string_payload_t;
+  void wasi_cache_list_tuple2_string_payload_free(wasi_cache_list_tuple2_string_payload_t *ptr);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      int64_t ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_s64_error_t;
+  void wasi_cache_expected_s64_error_free(wasi_cache_expected_s64_error_t *ptr);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      bool ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_bool_error_t;
+  void wasi_cache_expected_bool_error_free(wasi_cache_expected_bool_error_t *ptr);
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      wasi_cache_key_page_t ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_key_page_error_t;
+  void wasi_cache_expected_key_page_error_free(wasi_cache_expected_key_page_error_t *ptr);
+  typedef struct {
+    // `true` if `val` is present, `false` otherwise
+    bool tag;
+    wasi_cache_lifetime_t val;
+  } wasi_cache_option_lifetime_t;
+  typedef struct {
+    // 0 if `val` is `ok`, 1 otherwise
+    uint8_t tag;
+    union {
+      wasi_cache_option_lifetime_t ok;
+      wasi_cache_error_t err;
+    } val;
+  } wasi_cache_expected_option_lifetime_error_t;
+  void wasi_cache_expected_option_lifetime_error_free(wasi_cache_expected_option_lifetime_error_t *ptr);
+  void wasi_cache_set(wasi_cache_string_t *key, wasi_cache_payload_t *value, wasi_cache_option_u32_t *ttl, wasi_cache_expected_void_error_t *ret0);
+  void wasi_cache_get(wasi_cache_string_t *key, wasi_cache_expected_option_payload_error_t *ret0);
+  void wasi_cache_delete(wasi_cache_string_t *key, wasi_cache_expected_void_error_t *ret0);
+  void wasi_cache_get_many(wasi_cache_list_string_t *keys, wasi_cache_expected_list_option_payload_error_t *ret0);
+  void wasi_cache_set_many(wasi_cache_list_tuple2_string_payload_t *entries, wasi_cache_option_u32_t *ttl, wasi_cache_expected_void_error_t *ret0);
+  void wasi_cache_delete_many(wasi_cache_list_string_t *keys, wasi_cache_expected_void_error_t *ret0);
+  void wasi_cache_increment(wasi_cache_string_t *key, int64_t delta, wasi_cache_expected_s64_error_t *ret0);
+  void wasi_cache_compare_and_swap(wasi_cache_string_t *key, wasi_cache_option_payload_t *old, wasi_cache_payload_t *value, wasi_cache_expected_bool_error_t *ret0);
+  void wasi_cache_list_keys(wasi_cache_string_t *prefix, wasi_cache_option_string_t *cursor, uint32_t limit, wasi_cache_expected_key_page_error_t *ret0);
+  void wasi_cache_get_ttl(wasi_cache_string_t *key, wasi_cache_expected_option_lifetime_error_t *ret0);
   #ifdef __cplusplus
 }
 #endif
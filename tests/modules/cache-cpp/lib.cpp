@@ -7,9 +7,14 @@
 #include "bindings/wasi-cache.h"
 #include "bindings/test.h"
 
-// wit-bindgen doesn’t create a constant for the 
-// “success” case of the `unexpected`, so define one ourselves:
-const wasi_cache_error_t SUCCESS_VALUE = -1;
+// Print the message of a cache error and release it.
+// Every error case contains a message, so any of them can be used to read it.
+static void print_error(const char* op, wasi_cache_error_t* err)
+{
+    wasi_cache_string_t* msg = &err->val.other;
+    fprintf(stderr, "Failed to %s value (error %d): %.*s\n", op, err->tag, (int)msg->len, msg->ptr);
+    wasi_cache_error_free(err);
+}
 
 test_error_t test_test(void)
 {
@@ -27,41 +32,56 @@ test_error_t test_test(void)
         .len = strlen(value),
     };
 
-    auto result = wasi_cache_set(&skey, &svalue, nullptr);
-    if (result != SUCCESS_VALUE) {
-        fprintf(stderr, "Failed to set value\n");
+    wasi_cache_option_u32_t ttl{};
+    wasi_cache_expected_void_error_t result{};
+    wasi_cache_set(&skey, &svalue, &ttl, &result);
+    if (result.tag) {
+        print_error("set", &result.val.err);
         return TEST_ERROR_FAILURE;
     }
 
-    wasi_cache_payload_t ret{};
-    result = wasi_cache_get(&skey, &ret);
-    if (result != SUCCESS_VALUE) {
-        fprintf(stderr, "Failed to get value\n");
+    wasi_cache_expected_option_payload_error_t ret{};
+    wasi_cache_get(&skey, &ret);
+    if (ret.tag) {
+        print_error("get", &ret.val.err);
         return TEST_ERROR_FAILURE;
     }
 
-    if (ret.ptr == nullptr) {
+    if (!ret.val.ok.tag) {
         fprintf(stderr, "No value returned, failing test\n");
         return TEST_ERROR_FAILURE;
     }
 
-    printf("Retrieved from `%s`: `%s`\n", key, (char*)ret.ptr);
+    wasi_cache_payload_t* retrieved = &ret.val.ok.val;
+    printf("Retrieved from `%s`: `%.*s`\n", key, (int)retrieved->len, (char*)retrieved->ptr);
 
-    if (svalue.len != ret.len ||
-        memcmp(svalue.ptr, ret.ptr, svalue.len) != 0) {
+    if (svalue.len != retrieved->len ||
+        memcmp(svalue.ptr, retrieved->ptr, svalue.len) != 0) {
         fprintf(stderr, "Values don't match, failing test\n");
         return TEST_ERROR_FAILURE;
     }
 
-    result = wasi_cache_delete(&skey);
-    if (result != SUCCESS_VALUE) {
-        fprintf(stderr, "Failed to delete value\n");
+    wasi_cache_delete(&skey, &result);
+    if (result.tag) {
+        print_error("delete", &result.val.err);
+        return TEST_ERROR_FAILURE;
+    }
+
+    wasi_cache_expected_option_payload_error_t deleted{};
+    wasi_cache_get(&skey, &deleted);
+    if (deleted.tag) {
+        print_error("get deleted", &deleted.val.err);
+        return TEST_ERROR_FAILURE;
+    }
+
+    if (deleted.val.ok.tag) {
+        fprintf(stderr, "Value returned after deletion, failing test\n");
         return TEST_ERROR_FAILURE;
     }
 
     free(svalue.ptr);
     free(skey.ptr);
-    free(ret.ptr);
+    wasi_cache_expected_option_payload_error_free(&ret);
 
     printf("Test was successful\n");
     return TEST_ERROR_SUCCESS;
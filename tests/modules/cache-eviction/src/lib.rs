@@ -0,0 +1,60 @@
+//! Eviction test for the implementations of the cache interface that are
+//! bounded to a maximum number of entries, which evict the least recently
+//! used entries. The limit of the implementation, which must be at least 3,
+//! is passed to the module in the `MAX_ENTRIES` environment variable.
+
+wit_bindgen_rust::import!("../../../wit/ephemeral/wasi-cache.wit");
+wit_bindgen_rust::export!("../../test.wit");
+
+/// The environment variable containing the maximum number of entries.
+const MAX_ENTRIES_ENV: &str = "MAX_ENTRIES";
+
+/// The prefix of every key used by the test.
+const PREFIX: &str = "eviction-";
+
+struct Test {}
+
+impl test::Test for Test {
+    fn test() -> Result<(), test::Error> {
+        let max = std::env::var(MAX_ENTRIES_ENV)
+            .ok()
+            .and_then(|v| v.parse::<usize>().ok())
+            .filter(|max| *max >= 3)
+            .ok_or(test::Error::Failure)?;
+        let keys = (0..max + 2)
+            .map(|i| format!("{}{}", PREFIX, i))
+            .collect::<Vec<_>>();
+        let keys = keys.iter().map(String::as_str).collect::<Vec<_>>();
+        wasi_cache::delete_many(&keys)?;
+
+        println!("cache_eviction:: filling the cache with {} entries", max);
+        for key in &keys[..max] {
+            wasi_cache::set(key, key.as_bytes(), None)?;
+        }
+
+        // Reading the oldest entry makes it the most recently used one.
+        assert!(wasi_cache::get(keys[0])?.is_some());
+
+        println!("cache_eviction:: writing 2 entries past the limit");
+        for key in &keys[max..] {
+            wasi_cache::set(key, key.as_bytes(), None)?;
+        }
+
+        for (i, key) in keys.iter().enumerate() {
+            let evicted = i == 1 || i == 2;
+            let value = wasi_cache::get(key)?;
+            println!("cache_eviction:: key {} present: {}", key, value.is_some());
+            assert_eq!(!evicted, value.is_some(), "key {}", key);
+        }
+
+        wasi_cache::delete_many(&keys)?;
+        Ok(())
+    }
+}
+
+impl From<wasi_cache::Error> for test::Error {
+    fn from(e: wasi_cache::Error) -> Self {
+        println!("cache_eviction:: unexpected error {:?}", e);
+        Self::Failure
+    }
+}
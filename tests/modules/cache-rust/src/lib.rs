@@ -12,7 +12,7 @@ impl test::Test for Test {
         wasi_cache::set(key, &value.as_bytes(), None)?;
 
         println!("cache_rust_test:: reading from key {}", key);
-        let res = wasi_cache::get(key)?;
+        let res = wasi_cache::get(key)?.expect("value should be present");
         assert_eq!(value.as_bytes(), res);
 
         println!(
@@ -25,8 +25,176 @@ impl test::Test for Test {
 
         println!("cache_rust_test:: reading from key {} after deletion", key);
         let res = wasi_cache::get(key)?;
-        let empty: &[u8] = &[];
-        assert_eq!(empty, res.as_slice());
+        assert_eq!(None, res);
+
+        println!("cache_rust_test:: deleting missing key {}", key);
+        wasi_cache::delete(key)?;
+        wasi_cache::delete_many(&[key, "never_set"])?;
+        assert_eq!(None, wasi_cache::get(key)?);
+
+        println!("cache_rust_test:: writing empty value to key {}", key);
+        wasi_cache::set(key, &[], None)?;
+        let res = wasi_cache::get(key)?;
+        assert_eq!(Some(Vec::new()), res);
+        wasi_cache::delete(key)?;
+
+        let entries: &[(&str, &[u8])] = &[("frodo", b"Ring-bearer"), ("sam", b"Gardener")];
+        let keys = ["frodo", "sam", "gollum"];
+
+        println!("cache_rust_test:: writing {} entries", entries.len());
+        wasi_cache::set_many(entries, None)?;
+
+        println!("cache_rust_test:: reading keys {:?}", keys);
+        let res = wasi_cache::get_many(&keys)?;
+        assert_eq!(
+            vec![
+                Some(b"Ring-bearer".to_vec()),
+                Some(b"Gardener".to_vec()),
+                None
+            ],
+            res
+        );
+
+        println!("cache_rust_test:: deleting keys {:?}", keys);
+        wasi_cache::delete_many(&keys)?;
+        let res = wasi_cache::get_many(&keys)?;
+        assert_eq!(vec![None, None, None], res);
+
+        let counter = "ents_marching_on_isengard";
+        wasi_cache::delete(counter)?;
+
+        println!("cache_rust_test:: incrementing key {}", counter);
+        assert_eq!(1, wasi_cache::increment(counter, 1)?);
+        assert_eq!(11, wasi_cache::increment(counter, 10)?);
+        assert_eq!(Some(b"11".to_vec()), wasi_cache::get(counter)?);
+
+        println!("cache_rust_test:: compare and swap key {}", counter);
+        assert!(!wasi_cache::compare_and_swap(
+            counter,
+            Some("0".as_bytes()),
+            "1".as_bytes()
+        )?);
+        assert!(wasi_cache::compare_and_swap(
+            counter,
+            Some("11".as_bytes()),
+            "12".as_bytes()
+        )?);
+        assert_eq!(Some(b"12".to_vec()), wasi_cache::get(counter)?);
+
+        wasi_cache::delete(counter)?;
+        assert!(wasi_cache::compare_and_swap(counter, None, "1".as_bytes())?);
+        assert!(!wasi_cache::compare_and_swap(
+            counter,
+            None,
+            "2".as_bytes()
+        )?);
+        wasi_cache::delete(counter)?;
+
+        let entries: &[(&str, &[u8])] = &[
+            ("fellowship:aragorn", b"Ranger"),
+            ("fellowship:gimli", b"Dwarf"),
+            ("fellowship:legolas", b"Elf"),
+        ];
+        wasi_cache::set_many(entries, None)?;
+        wasi_cache::set("sauron", b"Dark Lord", None)?;
+
+        println!("cache_rust_test:: listing keys with prefix fellowship:");
+        let mut keys = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = wasi_cache::list_keys("fellowship:", cursor.as_deref(), 2)?;
+            keys.extend(page.keys);
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        // Some implementations may return a key in more than one page.
+        keys.sort();
+        keys.dedup();
+        assert_eq!(
+            vec![
+                "fellowship:aragorn",
+                "fellowship:gimli",
+                "fellowship:legolas"
+            ],
+            keys
+        );
+
+        wasi_cache::delete_many(&[
+            "fellowship:aragorn",
+            "fellowship:gimli",
+            "fellowship:legolas",
+        ])?;
+        wasi_cache::delete("sauron")?;
+
+        // Keys that look like paths are stored like any other key, and keys
+        // too long to be used as file names do not collide with each other.
+        let long = "the_road_goes_ever_on_".repeat(10);
+        let keys = [
+            "../x".to_string(),
+            "../../etc/passwd".to_string(),
+            "/etc/passwd".to_string(),
+            "..".to_string(),
+            ".".to_string(),
+            "..\\x".to_string(),
+            format!("{}and_on", long),
+            format!("{}and_on_", long),
+            "/".repeat(100),
+        ];
+        println!(
+            "cache_rust_test:: writing {} path-like and long keys",
+            keys.len()
+        );
+        for key in &keys {
+            wasi_cache::set(key, key.as_bytes(), None)?;
+        }
+        for key in &keys {
+            assert_eq!(Some(key.as_bytes().to_vec()), wasi_cache::get(key)?);
+        }
+
+        let mut listed = Vec::new();
+        let mut cursor = None;
+        loop {
+            let page = wasi_cache::list_keys(&long, cursor.as_deref(), 10)?;
+            listed.extend(page.keys);
+            cursor = page.cursor;
+            if cursor.is_none() {
+                break;
+            }
+        }
+        listed.sort();
+        listed.dedup();
+        assert_eq!(keys[6..8].to_vec(), listed);
+
+        for key in &keys {
+            wasi_cache::delete(key)?;
+            assert_eq!(None, wasi_cache::get(key)?);
+        }
+
+        let rings = "rings_for_the_elven_kings";
+        println!(
+            "cache_rust_test:: writing key {} with a time-to-live",
+            rings
+        );
+        wasi_cache::set(rings, b"Three", Some(60))?;
+        assert_eq!(Some(b"Three".to_vec()), wasi_cache::get(rings)?);
+        assert!(matches!(
+            wasi_cache::get_ttl(rings)?,
+            Some(wasi_cache::Lifetime::Remaining(s)) if s > 0 && s <= 60
+        ));
+
+        // Overwriting an entry without a time-to-live makes it persistent,
+        // and overwriting it with a zero time-to-live expires it immediately.
+        wasi_cache::set(rings, b"Three", None)?;
+        assert!(matches!(
+            wasi_cache::get_ttl(rings)?,
+            Some(wasi_cache::Lifetime::Persistent)
+        ));
+        wasi_cache::set(rings, b"Three", Some(0))?;
+        assert_eq!(None, wasi_cache::get(rings)?);
+        assert!(wasi_cache::get_ttl(rings)?.is_none());
+        wasi_cache::delete(rings)?;
 
         Ok(())
     }